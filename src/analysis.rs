@@ -0,0 +1,203 @@
+// Graph queries over a conversation, used by the editor's filters and by the validator
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{Dialogue, Dialogues, PLACEHOLDER_INTRO, PLACEHOLDER_SPEAKER};
+
+// The entry point every conversation file is expected to have
+pub const START_DIALOGUE: &str = "Start";
+
+impl Dialogue {
+    // IDs this dialogue can move to, in option order, skipping unset links
+    pub fn links(&self) -> impl Iterator<Item = &str> {
+        self.options.iter().flat_map(|option| {
+            [&option.success_dialogue, &option.failure_dialogue]
+                .into_iter()
+                .filter_map(|target| target.as_deref())
+                .filter(|target| !target.is_empty())
+        })
+    }
+
+    pub fn has_challenge(&self) -> bool {
+        self.options.iter().any(|option| option.challenge_attribute.is_some())
+    }
+
+    // Still showing what create_dialogue put in: the placeholder speaker or intro
+    pub fn is_placeholder(&self) -> bool {
+        self.speaker == PLACEHOLDER_SPEAKER || self.intro == PLACEHOLDER_INTRO
+    }
+
+    // Case-insensitive match against the ID, speakers and every piece of text in the dialogue
+    pub fn matches_search(&self, id: &str, query: &str) -> bool {
+        let query = query.to_lowercase();
+        let contains = |text: &str| text.to_lowercase().contains(&query);
+
+        contains(id)
+            || contains(&self.speaker)
+            || contains(&self.intro)
+            || self.options.iter().any(|option| contains(&option.description))
+            || self.passive_check.iter().any(|check| {
+                [&check.success_text, &check.failure_text, &check.speaker]
+                    .into_iter()
+                    .flatten()
+                    .any(|text| contains(text))
+            })
+    }
+}
+
+// How far along the writing of a dialogue is
+#[derive(Debug, Clone, PartialEq)]
+pub enum Completeness {
+    Stub,                    // No text yet: the intro is empty or still create_dialogue's placeholder
+    Incomplete(Vec<String>), // Has text, but with the parts still missing
+    Complete,
+}
+
+impl Dialogue {
+    // A dialogue with no options leaves the player stuck, so every dialogue needs at least one
+    pub fn completeness(&self) -> Completeness {
+        if self.intro.trim().is_empty() || self.intro == PLACEHOLDER_INTRO {
+            return Completeness::Stub;
+        }
+
+        let mut missing = vec![];
+        if self.speaker == PLACEHOLDER_SPEAKER {
+            missing.push("the speaker is still the placeholder".to_string());
+        }
+        if self.options.is_empty() {
+            missing.push("no options".to_string());
+        }
+        for (i, option) in self.options.iter().enumerate() {
+            if option.description.trim().is_empty() {
+                missing.push(format!("option {} has no text", i + 1));
+            }
+            if option.challenge_attribute.is_some() && option.challenge_number.is_none() {
+                missing.push(format!("option {} has a challenge with no difficulty", i + 1));
+            }
+        }
+        if missing.is_empty() {
+            Completeness::Complete
+        } else {
+            Completeness::Incomplete(missing)
+        }
+    }
+
+    pub fn needs_writing(&self) -> bool {
+        self.completeness() != Completeness::Complete
+    }
+}
+
+// Where a player can enter the file: "Start" if it has one, otherwise every dialogue nothing links to
+// (conversation files like clock.json are entered from another conversation's options)
+pub fn entry_points(dialogues: &Dialogues) -> Vec<String> {
+    if dialogues.contains_key(START_DIALOGUE) {
+        return vec![START_DIALOGUE.to_string()];
+    }
+    let linked: HashSet<&str> = dialogues.values().flat_map(Dialogue::links).collect();
+    dialogues.keys().filter(|id| !linked.contains(id.as_str())).cloned().collect()
+}
+
+// File order that follows the scene: each entry point, then its branches depth first,
+// so every branch sits together; dialogues nothing reaches keep their order at the end
+pub fn flow_order(dialogues: &Dialogues) -> Vec<String> {
+    fn visit(dialogues: &Dialogues, id: &str, seen: &mut HashSet<String>, order: &mut Vec<String>) {
+        let Some(dialogue) = dialogues.get(id) else {
+            return;
+        };
+        if !seen.insert(id.to_string()) {
+            return;
+        }
+        order.push(id.to_string());
+        for target in dialogue.links() {
+            visit(dialogues, target, seen, order);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut order = vec![];
+    for entry in entry_points(dialogues) {
+        visit(dialogues, &entry, &mut seen, &mut order);
+    }
+    for id in dialogues.keys() {
+        if !seen.contains(id) {
+            order.push(id.clone());
+        }
+    }
+    order
+}
+
+// Every dialogue reachable from the file's entry points
+pub fn reachable(dialogues: &Dialogues) -> HashSet<String> {
+    reachable_from(dialogues, &entry_points(dialogues))
+}
+
+// Every dialogue that can be reached from the given starts by following option links
+pub fn reachable_from(dialogues: &Dialogues, starts: &[String]) -> HashSet<String> {
+    let mut reached = HashSet::new();
+    let mut queue: VecDeque<String> = starts.iter().cloned().collect();
+
+    while let Some(id) = queue.pop_front() {
+        let Some(dialogue) = dialogues.get(&id) else {
+            continue;
+        };
+        if !reached.insert(id) {
+            continue;
+        }
+        for target in dialogue.links() {
+            if !reached.contains(target) {
+                queue.push_back(target.to_string());
+            }
+        }
+    }
+    reached
+}
+
+// Links from this dialogue that name a dialogue missing from this file
+// Links into other conversations ("Intro/Start") are checked against the project by project::broken_links
+pub fn dangling_links<'a>(dialogue: &'a Dialogue, dialogues: &Dialogues) -> Vec<&'a str> {
+    dialogue
+        .links()
+        .filter(|target| !target.contains('/') && !dialogues.contains_key(*target))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DialogueOption;
+
+    fn written(speaker: &str, intro: &str, options: Vec<DialogueOption>) -> Dialogue {
+        Dialogue {
+            speaker: speaker.to_string(),
+            intro: intro.to_string(),
+            options,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn completeness_names_what_is_missing() {
+        assert_eq!(written("Clock", "  ", vec![]).completeness(), Completeness::Stub);
+        assert_eq!(written("Clock", PLACEHOLDER_INTRO, vec![DialogueOption::default()]).completeness(), Completeness::Stub);
+        assert_eq!(written("Clock", "Tick.", vec![DialogueOption::default()]).completeness(), Completeness::Complete);
+
+        let challenge = DialogueOption {
+            description: " ".to_string(),
+            challenge_attribute: Some("Logic".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            written(PLACEHOLDER_SPEAKER, "Tick.", vec![DialogueOption::default(), challenge]).completeness(),
+            Completeness::Incomplete(vec![
+                "the speaker is still the placeholder".to_string(),
+                "option 2 has no text".to_string(),
+                "option 2 has a challenge with no difficulty".to_string(),
+            ])
+        );
+        assert_eq!(
+            written("Clock", "Tick.", vec![]).completeness(),
+            Completeness::Incomplete(vec!["no options".to_string()])
+        );
+        assert!(!written("Clock", "Tick.", vec![DialogueOption::default()]).needs_writing());
+    }
+}
@@ -0,0 +1,1897 @@
+// The dialogue editor: a project panel listing locations and conversations, one tab per open
+// conversation file, and the fields of the selected dialogue
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime};
+
+use eframe::egui;
+use shadow_soldiers_dialog::analysis::{flow_order, reachable, Completeness};
+use shadow_soldiers_dialog::history::{EditCommand, EditHistory};
+use shadow_soldiers_dialog::loading::{load_dialogue_file, LoadIssue, Severity, Strictness};
+use shadow_soldiers_dialog::project::{broken_links, split_link, Project};
+use shadow_soldiers_dialog::markup::{MarkupCache, MarkupContext};
+use shadow_soldiers_dialog::scene::{show_dialogue, ImageCache};
+use shadow_soldiers_dialog::validation::dialogue_issues;
+use shadow_soldiers_dialog::speakers::{load_speakers, save_speakers, speaker_path, unknown_speakers, Speaker, Speakers};
+use shadow_soldiers_dialog::*;
+
+pub const DEFAULT_DIALOGUE_FILE: &str = "src/dialogues/clock.json";
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct DialogueEditorApp {
+    project_path: String,
+    project: Option<Project>,                       // Locations and conversations shown in the project panel
+    saved_project: Option<Project>,                 // The project file as last loaded or saved
+    locations: LocationEditor,                      // State of the locations window
+    disk_conversations: HashMap<String, Dialogues>, // Every conversation as saved, for links into files that aren't open
+    tabs: Vec<FileTab>,                             // Open dialogue files
+    active: usize,                                  // Index of the tab being edited
+    items: Vec<String>,                             // Item catalogue offered by the item picker
+    speakers: Speakers,                             // Speaker catalogue offered by the speaker pickers
+    saved_speakers: Speakers,                       // The speaker catalogue as last loaded or saved
+    speaker_editor: SpeakerEditor,                  // State of the speakers window
+    images: ImageCache,                             // Backgrounds and portraits for the preview pane
+    markup: MarkupCache,                            // Text parsed for the preview pane, kept between frames
+    show_preview: bool,                             // Whether the preview pane is open
+    filters: ListFilters,                           // Search and filters for the dialogue list
+    transfer: Option<Transfer>,                     // Copy or move to another file waiting on a target
+    closing: bool,                                  // Window close waiting on the unsaved-changes prompt
+    allow_close: bool,                              // Set once the user chose to close despite unsaved changes
+    title: String,                                  // Window title last sent to the viewport
+    last_disk_check: Instant,
+    last_autosave: Instant,
+}
+
+// One open dialogue file with its own selection, history and save state
+struct FileTab {
+    file_path: String,
+    conversation: Option<String>,          // Conversation ID in the project, which other files use in links
+    dialogues: Dialogues,                  // Dialogues being edited
+    saved: Dialogues,                      // The file as last loaded or saved, for the dirty check and merges
+    disk_modified: Option<SystemTime>,     // Modification time of the file when we last read or wrote it
+    autosaved: Option<Dialogues>,          // What the recovery file holds, to skip rewriting it unchanged
+    prompt: Option<Prompt>,                // Question about this file blocking it until answered
+    selected_dialogue: Option<String>,     // Currently selected dialogue ID
+    temp_id: String,                       // Temporary field for editing dialogue ID
+    status: Option<String>,                // Result of the last command, shown under the toolbar
+    pending_delete: Option<PendingDelete>, // Delete waiting on confirmation
+    usages_of: Option<String>,             // Dialogue whose incoming links are listed in the usages window
+    history: EditHistory,                  // Undo/redo stack for every change to the dialogues
+    pending_edit: Option<PendingEdit>,     // Field edits not yet recorded, coalesced per focused widget
+    load_issues: Vec<LoadIssue>,           // What couldn't be read from the file; errors block saving, unknown fields need a confirm
+}
+
+#[derive(Default)]
+struct LocationEditor {
+    open: bool,
+    selected: Option<String>,
+    temp_name: String,    // Rename field for the selected location
+    new_location: String, // Name typed for the next location
+    status: Option<String>,
+}
+
+#[derive(Default)]
+struct SpeakerEditor {
+    open: bool,
+    new_speaker: String, // ID typed for the next speaker
+    status: Option<String>,
+}
+
+#[derive(Default)]
+struct ListFilters {
+    search: String,
+    has_challenge: bool,
+    has_passive_check: bool,
+    unreachable: bool,
+    dangling_links: bool,
+    unknown_speaker: bool,
+    needs_writing: bool, // Stubs and incomplete dialogues
+    hidden: bool,
+    sort: ListSort,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum ListSort {
+    #[default]
+    File, // The order dialogues are saved in, which the list can rearrange
+    Id,
+    Speaker,
+}
+
+impl ListFilters {
+    // Every ticked filter must hold, on top of the search text
+    fn matches(
+        &self,
+        id: &str,
+        dialogue: &Dialogue,
+        reachable: &HashSet<String>,
+        dialogues: &Dialogues,
+        conversations: &HashMap<String, &Dialogues>,
+        speakers: &Speakers,
+    ) -> bool {
+        (self.search.is_empty() || dialogue.matches_search(id, &self.search))
+            && (!self.has_challenge || dialogue.has_challenge())
+            && (!self.has_passive_check || !dialogue.passive_check.is_empty())
+            && (!self.unreachable || !reachable.contains(id))
+            && (!self.dangling_links || !broken_links(dialogue, dialogues, conversations).is_empty())
+            && (!self.unknown_speaker || !unknown_speakers(dialogue, speakers).is_empty())
+            && (!self.needs_writing || dialogue.needs_writing())
+            && (!self.hidden || dialogue.is_hidden)
+    }
+}
+
+enum Prompt {
+    CloseTab,
+    ChangedOnDisk,
+    Recovery(Dialogues),
+}
+
+struct PendingEdit {
+    id: String,
+    before: Dialogue,
+    widget: egui::Id, // The widget being typed in or dragged; losing it closes the edit
+}
+
+struct PendingDelete {
+    id: String,
+    redirect_to: String, // Empty means the incoming links get cleared
+}
+
+// An option somewhere in the project that links to a dialogue of the active tab
+struct Usage {
+    conversation: Option<String>, // The file the option is in, None for the dialogue's own file
+    reference: DialogueReference,
+    description: String,
+}
+
+impl Usage {
+    fn source(&self) -> String {
+        match &self.conversation {
+            Some(conversation) => format!("{}/{}", conversation, self.reference.dialogue_id),
+            None => self.reference.dialogue_id.clone(),
+        }
+    }
+}
+
+// A redirect target as another file has to write it
+fn qualified(conversation: &str, link: &str) -> String {
+    match split_link(link) {
+        (Some(_), _) => link.to_string(),
+        (None, id) => format!("{}/{}", conversation, id),
+    }
+}
+
+enum DeleteChoice {
+    Redirect(String),
+    Clear,
+    Cancel,
+}
+
+struct Transfer {
+    id: String,
+    target: Option<usize>, // Tab the dialogue goes to
+}
+
+fn modified_time(file_path: &str) -> Option<SystemTime> {
+    std::fs::metadata(file_path).ok()?.modified().ok()
+}
+
+impl DialogueEditorApp {
+    pub fn new(project_path: &str, file_path: &str) -> Self {
+        let project = Project::load(project_path).ok();
+        let speakers = load_speakers(&speaker_path(project_path));
+        let disk_conversations = project
+            .as_ref()
+            .map(|project| project.load_conversations(project_path))
+            .unwrap_or_default();
+
+        let mut app = Self {
+            project_path: project_path.to_string(),
+            saved_project: project.clone(),
+            project,
+            locations: LocationEditor::default(),
+            disk_conversations,
+            tabs: vec![],
+            active: 0,
+            items: load_items("src/items.json"),
+            saved_speakers: speakers.clone(),
+            speakers,
+            speaker_editor: SpeakerEditor::default(),
+            images: ImageCache::new(Project::base_dir(project_path)),
+            markup: MarkupCache::new(),
+            show_preview: true,
+            filters: ListFilters::default(),
+            transfer: None,
+            closing: false,
+            allow_close: false,
+            title: String::new(),
+            last_disk_check: Instant::now(),
+            last_autosave: Instant::now(),
+        };
+        app.open_file(file_path);
+        app
+    }
+
+    // Switches to the file's tab, opening it first if needed
+    fn open_file(&mut self, file_path: &str) {
+        if let Some(index) = self.tabs.iter().position(|tab| tab.file_path == file_path) {
+            self.active = index;
+            return;
+        }
+        let conversation = self
+            .project
+            .as_ref()
+            .and_then(|project| project.conversation_for_file(&self.project_path, file_path));
+        self.tabs.push(FileTab::open(file_path, conversation));
+        self.active = self.tabs.len() - 1;
+    }
+
+    fn speakers_dirty(&self) -> bool {
+        self.speakers != self.saved_speakers
+    }
+
+    fn save_speakers(&mut self) -> bool {
+        let path = speaker_path(&self.project_path);
+        match save_speakers(&self.speakers, &path) {
+            Ok(()) => {
+                self.saved_speakers = self.speakers.clone();
+                self.speaker_editor.status = Some(format!("Saved {}", path));
+                true
+            }
+            Err(err) => {
+                self.speaker_editor.status = Some(format!("Save failed: {}", err));
+                false
+            }
+        }
+    }
+
+    fn project_dirty(&self) -> bool {
+        self.project != self.saved_project
+    }
+
+    fn save_project(&mut self) -> bool {
+        let Some(project) = &self.project else {
+            return true;
+        };
+        match project.save(&self.project_path) {
+            Ok(()) => {
+                self.saved_project = self.project.clone();
+                self.locations.status = Some(format!("Saved {}", self.project_path));
+                // Attached or renamed conversations change which files the link checks read
+                self.disk_conversations = project.load_conversations(&self.project_path);
+                for tab in &mut self.tabs {
+                    tab.conversation = project.conversation_for_file(&self.project_path, &tab.file_path);
+                }
+                true
+            }
+            Err(err) => {
+                self.locations.status = Some(format!("Save failed: {}", err));
+                false
+            }
+        }
+    }
+
+    // Every conversation in the project, using the open tab's unsaved version where there is one
+    fn conversation_map(&self) -> HashMap<String, &Dialogues> {
+        let mut conversations: HashMap<String, &Dialogues> =
+            self.disk_conversations.iter().map(|(id, dialogues)| (id.clone(), dialogues)).collect();
+        for tab in &self.tabs {
+            if let Some(conversation) = &tab.conversation {
+                conversations.insert(conversation.clone(), &tab.dialogues);
+            }
+        }
+        conversations
+    }
+
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        // Consumed before any widget runs, so a focused text field doesn't apply its own undo as well
+        let redo_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z);
+        let undo_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+
+        let Some(tab) = self.tabs.get_mut(self.active) else {
+            return;
+        };
+        if ctx.input_mut(|input| input.consume_shortcut(&redo_shortcut)) {
+            tab.redo();
+        } else if ctx.input_mut(|input| input.consume_shortcut(&undo_shortcut)) {
+            tab.undo();
+        }
+    }
+
+    // Keeps the title, autosaves, close prompt and on-disk copies in step with the edits
+    fn watch_files(&mut self, ctx: &egui::Context) {
+        let any_dirty = self.tabs.iter().any(FileTab::is_dirty) || self.project_dirty() || self.speakers_dirty();
+
+        let file_name = self
+            .tabs
+            .get(self.active)
+            .and_then(|tab| std::path::Path::new(&tab.file_path).file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let title = format!("Dialogue Editor - {}{}", file_name, if any_dirty { " *" } else { "" });
+        if title != self.title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+
+        if ctx.input(|input| input.viewport().close_requested()) && any_dirty && !self.allow_close {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.closing = true;
+        }
+
+        if self.last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+            self.last_autosave = Instant::now();
+            for tab in &mut self.tabs {
+                tab.autosave();
+            }
+        }
+
+        if self.last_disk_check.elapsed() >= DISK_CHECK_INTERVAL {
+            self.last_disk_check = Instant::now();
+            for tab in &mut self.tabs {
+                tab.check_disk();
+            }
+        }
+
+        // Keep the timers running while the window is idle
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+
+    fn display_project_panel(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Project");
+
+        let label = format!("Edit Speakers{}", if self.speakers_dirty() { " *" } else { "" });
+        if ui.button(label).clicked() {
+            self.speaker_editor.open = !self.speaker_editor.open;
+        }
+
+        let Some(project) = &self.project else {
+            ui.weak(format!("No project file at {}", self.project_path));
+            if ui.button("Create Project").clicked() {
+                self.project = Some(Project::default());
+                self.locations.open = true;
+            }
+            return;
+        };
+
+        let label = format!("Edit Locations{}", if self.project_dirty() { " *" } else { "" });
+        if ui.button(label).clicked() {
+            self.locations.open = !self.locations.open;
+        }
+
+        let active_path = self.tabs.get(self.active).map(|tab| tab.file_path.clone());
+        let mut open = None;
+        let files = project.conversation_files(&self.project_path);
+        for location in project.locations.keys() {
+            egui::CollapsingHeader::new(location)
+                .default_open(true)
+                .show(ui, |ui| {
+                    for file in files.iter().filter(|file| file.location == *location) {
+                        let is_active = active_path.as_deref() == Some(file.path.as_str());
+                        if ui.selectable_label(is_active, &file.conversation).on_hover_text(&file.path).clicked() {
+                            open = Some(file.path.clone());
+                        }
+                    }
+                });
+        }
+
+        if let Some(path) = open {
+            self.open_file(&path);
+        }
+    }
+
+    fn display_tabs(&mut self, ui: &mut egui::Ui) {
+        let mut activate = None;
+        let mut close = None;
+        ui.horizontal_wrapped(|ui| {
+            for (index, tab) in self.tabs.iter().enumerate() {
+                let name = tab.conversation.clone().unwrap_or_else(|| tab.file_path.clone());
+                let label = format!("{}{}", name, if tab.is_dirty() { " *" } else { "" });
+                if ui.selectable_label(index == self.active, label).on_hover_text(&tab.file_path).clicked() {
+                    activate = Some(index);
+                }
+                if ui.small_button("x").on_hover_text("Close tab").clicked() {
+                    close = Some(index);
+                }
+                ui.separator();
+            }
+        });
+
+        if let Some(index) = activate {
+            self.active = index;
+        }
+        if let Some(index) = close {
+            if self.tabs[index].is_dirty() {
+                self.active = index;
+                self.tabs[index].prompt = Some(Prompt::CloseTab);
+            } else {
+                self.close_tab(index);
+            }
+        }
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        self.tabs.remove(index);
+        if self.active >= index && self.active > 0 {
+            self.active -= 1;
+        }
+        self.transfer = None;
+    }
+
+    fn display_dialogue_list(&mut self, ui: &mut egui::Ui) {
+        ui.label("Available Dialogues:");
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            ui.text_edit_singleline(&mut self.filters.search);
+            egui::ComboBox::from_id_salt("dialogue_list_sort")
+                .selected_text(match self.filters.sort {
+                    ListSort::File => "File order",
+                    ListSort::Id => "Sort by ID",
+                    ListSort::Speaker => "Sort by speaker",
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filters.sort, ListSort::File, "File order");
+                    ui.selectable_value(&mut self.filters.sort, ListSort::Id, "Sort by ID");
+                    ui.selectable_value(&mut self.filters.sort, ListSort::Speaker, "Sort by speaker");
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.filters.has_challenge, "Has challenge");
+            ui.checkbox(&mut self.filters.has_passive_check, "Has passive check");
+            ui.checkbox(&mut self.filters.unreachable, "Unreachable");
+            ui.checkbox(&mut self.filters.dangling_links, "Dangling links");
+            ui.checkbox(&mut self.filters.unknown_speaker, "Unknown speaker");
+            ui.checkbox(&mut self.filters.needs_writing, "Needs writing");
+            ui.checkbox(&mut self.filters.hidden, "Hidden");
+        });
+
+        let Some(tab) = self.tabs.get(self.active) else {
+            return;
+        };
+        let conversations = self.conversation_map();
+        let reachable = reachable(&tab.dialogues);
+        let mut shown: Vec<(&String, &Dialogue)> = tab
+            .dialogues
+            .iter()
+            .filter(|(id, dialogue)| {
+                self.filters.matches(id, dialogue, &reachable, &tab.dialogues, &conversations, &self.speakers)
+            })
+            .collect();
+        match self.filters.sort {
+            ListSort::File => {}
+            ListSort::Id => shown.sort_by(|a, b| a.0.cmp(b.0)),
+            ListSort::Speaker => shown.sort_by(|a, b| (&a.1.speaker, a.0).cmp(&(&b.1.speaker, b.0))),
+        }
+        let shown: Vec<String> = shown.into_iter().map(|(id, _)| id.clone()).collect();
+        let total = tab.dialogues.len();
+        let has_other_tabs = self.tabs.len() > 1;
+
+        let tab = &mut self.tabs[self.active];
+        let mut to_delete = None;
+        let mut selected = None;
+        let mut reorder = None;
+        let mut usages_of = None;
+        let mut transfer = None;
+
+        ui.horizontal(|ui| {
+            ui.weak(format!("Showing {} of {}", shown.len(), total));
+            if ui
+                .button("Group by Flow")
+                .on_hover_text("Reorder the file so each branch follows the dialogue that leads to it")
+                .clicked()
+            {
+                reorder = Some(flow_order(&tab.dialogues));
+            }
+        });
+
+        // Moving rows only makes sense when the list shows the file order
+        // A row trades places with the row shown next to it; rows the filters hide stay where they are
+        let movable = self.filters.sort == ListSort::File;
+        for (row, id) in shown.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if movable {
+                    let previous = row.checked_sub(1).map(|row| &shown[row]);
+                    let next = shown.get(row + 1);
+                    if ui.add_enabled(previous.is_some(), egui::Button::new("⏶")).on_hover_text("Move up").clicked() {
+                        reorder = previous.map(|previous| tab.moved_order(id, previous, false));
+                    }
+                    if ui.add_enabled(next.is_some(), egui::Button::new("⏷")).on_hover_text("Move down").clicked() {
+                        reorder = next.map(|next| tab.moved_order(id, next, true));
+                    }
+                }
+
+                // Button to edit a dialogue
+                if ui.button(format!("Edit: {}", id)).clicked() {
+                    selected = Some(id.clone());
+                }
+
+                // Button to list every option that links here
+                if ui.button("Find Usages").clicked() {
+                    usages_of = Some(id.clone());
+                }
+
+                // Button to copy or move a dialogue into another open file
+                if ui.add_enabled(has_other_tabs, egui::Button::new("Copy/Move")).clicked() {
+                    transfer = Some(id.clone());
+                }
+
+                // Button to delete a dialogue
+                if ui.button("Delete").clicked() {
+                    to_delete = Some(id.clone());
+                }
+            });
+        }
+
+        if selected.is_some() {
+            tab.select(selected);
+        }
+        if usages_of.is_some() {
+            tab.usages_of = usages_of;
+        }
+        if let Some(order) = reorder {
+            tab.reorder_dialogues(order);
+        }
+
+        // Ask before deleting the selected dialogue
+        if let Some(id) = to_delete {
+            tab.pending_delete = Some(PendingDelete {
+                id,
+                redirect_to: String::new(),
+            });
+        }
+
+        if let Some(id) = transfer {
+            self.transfer = Some(Transfer { id, target: None });
+        }
+    }
+
+    fn display_editor(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let Some(tab) = self.tabs.get(self.active) else {
+            return;
+        };
+
+        let mut catalogue = EditorCatalogue::new(&tab.dialogues, &self.items);
+        if let Some(project) = &self.project {
+            catalogue.locations = project.location_names();
+        }
+        catalogue.speakers = self.speakers.keys().cloned().collect();
+        for (conversation, dialogues) in self.conversation_map() {
+            if tab.conversation.as_ref() != Some(&conversation) {
+                catalogue.add_conversation(&conversation, dialogues);
+            }
+        }
+
+        if let Some((old_id, new_id)) = self.tabs[self.active].display_editor(ui, ctx, &catalogue) {
+            self.rename_dialogue(&old_id, &new_id);
+        }
+    }
+
+    // Renames a dialogue of the active tab and points "Conversation/Old" links in the rest of the project at it
+    // Files with such links are opened in tabs, so the change can be reviewed, undone or saved there
+    fn rename_dialogue(&mut self, old_id: &str, new_id: &str) {
+        let tab = &self.tabs[self.active];
+        let valid = !new_id.is_empty() && old_id != new_id && !tab.dialogues.contains_key(new_id);
+        let conversation = tab.conversation.clone().filter(|_| valid);
+        if let Some(conversation) = &conversation {
+            self.open_tabs_linking_to(&format!("{}/{}", conversation, old_id));
+        }
+
+        let (before, rest) = self.tabs.split_at_mut(self.active);
+        let Some((tab, after)) = rest.split_first_mut() else {
+            return;
+        };
+        let mut others: Vec<&mut FileTab> = before.iter_mut().chain(after.iter_mut()).collect();
+        tab.rename_dialogue(old_id, new_id, conversation.as_deref(), &mut others);
+    }
+
+    // Opens, without switching to them, the project files whose saved version links to the target
+    fn open_tabs_linking_to(&mut self, link: &str) {
+        let Some(project) = &self.project else {
+            return;
+        };
+        for file in project.conversation_files(&self.project_path) {
+            let linking = self
+                .disk_conversations
+                .get(&file.conversation)
+                .is_some_and(|dialogues| !find_references(dialogues, link).is_empty());
+            if linking && !self.tabs.iter().any(|tab| tab.file_path == file.path) {
+                self.tabs.push(FileTab::open(&file.path, Some(file.conversation)));
+            }
+        }
+    }
+
+    fn open_conversation(&mut self, conversation: &str) {
+        let path = self.project.as_ref().and_then(|project| {
+            let files = project.conversation_files(&self.project_path);
+            files.into_iter().find(|file| file.conversation == conversation).map(|file| file.path)
+        });
+        if let Some(path) = path {
+            self.open_file(&path);
+        }
+    }
+
+    // Options that link to a dialogue of the active tab: in its own file by ID or "Conversation/ID",
+    // and in the rest of the project by "Conversation/ID"
+    fn usages(&self, id: &str) -> Vec<Usage> {
+        let Some(tab) = self.tabs.get(self.active) else {
+            return vec![];
+        };
+        let usage = |conversation: Option<&String>, dialogues: &Dialogues, reference: DialogueReference| Usage {
+            conversation: conversation.cloned(),
+            description: dialogues[&reference.dialogue_id].options[reference.option_index].description.clone(),
+            reference,
+        };
+        let mut usages: Vec<Usage> = find_references(&tab.dialogues, id)
+            .into_iter()
+            .map(|reference| usage(None, &tab.dialogues, reference))
+            .collect();
+        let Some(own) = &tab.conversation else {
+            return usages;
+        };
+
+        let link = format!("{}/{}", own, id);
+        let conversations = self.conversation_map();
+        let mut conversations: Vec<(&String, &&Dialogues)> = conversations.iter().collect();
+        conversations.sort_by_key(|(conversation, _)| *conversation);
+        for (conversation, dialogues) in conversations {
+            let file = (conversation != own).then_some(conversation);
+            usages.extend(find_references(dialogues, &link).into_iter().map(|reference| usage(file, dialogues, reference)));
+        }
+        usages
+    }
+
+    fn display_usages(&mut self, ctx: &egui::Context) {
+        let tab_index = self.active;
+        let Some(target) = self.tabs.get(tab_index).and_then(|tab| tab.usages_of.clone()) else {
+            return;
+        };
+
+        let usages = self.usages(&target);
+        let mut open = true;
+        let mut go_to = None;
+        egui::Window::new(format!("Usages of {}", target))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                if usages.is_empty() {
+                    ui.label("No options link to this dialogue.");
+                }
+                for usage in &usages {
+                    ui.horizontal(|ui| {
+                        if ui.button(usage.source()).clicked() {
+                            go_to = Some((usage.conversation.clone(), usage.reference.dialogue_id.clone()));
+                        }
+                        ui.label(format!(
+                            "option {} ({:?}): {}",
+                            usage.reference.option_index + 1,
+                            usage.reference.kind,
+                            usage.description
+                        ));
+                    });
+                }
+            });
+
+        if !open {
+            self.tabs[tab_index].usages_of = None;
+        }
+        if let Some((conversation, id)) = go_to {
+            if let Some(conversation) = conversation {
+                self.open_conversation(&conversation);
+            }
+            if let Some(tab) = self.tabs.get_mut(self.active) {
+                tab.select(Some(id));
+            }
+        }
+    }
+
+    fn confirm_delete(&mut self, ctx: &egui::Context) {
+        let Some(id) = self.tabs.get(self.active).and_then(|tab| tab.pending_delete.as_ref()).map(|pending| pending.id.clone()) else {
+            return;
+        };
+        let usages = self.usages(&id);
+        if let Some((id, redirect_to)) = self.tabs[self.active].confirm_delete(ctx, &usages) {
+            self.delete_dialogue(&id, redirect_to);
+        }
+    }
+
+    // Deletes a dialogue of the active tab; "Conversation/ID" links in the other files follow the file's own
+    // links, to the redirect or to nothing, and files with such links are opened so the change can be reviewed
+    fn delete_dialogue(&mut self, id: &str, redirect_to: Option<String>) {
+        let conversation = self.tabs[self.active].conversation.clone();
+        if let Some(conversation) = &conversation {
+            self.open_tabs_linking_to(&format!("{}/{}", conversation, id));
+        }
+
+        let (before, rest) = self.tabs.split_at_mut(self.active);
+        let Some((tab, after)) = rest.split_first_mut() else {
+            return;
+        };
+        if !tab.delete_dialogue(id, redirect_to.clone()) {
+            return;
+        }
+        let Some(conversation) = conversation else {
+            return;
+        };
+        let link = format!("{}/{}", conversation, id);
+        let target = redirect_to.map(|redirect_to| qualified(&conversation, &redirect_to));
+        let files = before
+            .iter_mut()
+            .chain(after.iter_mut())
+            .map(|other| other.rewrite_links(&link, target.as_deref()))
+            .filter(|rewritten| *rewritten > 0)
+            .count();
+        if files > 0 {
+            if let Some(status) = &mut tab.status {
+                status.push_str(&format!(", and links in {} other file(s)", files));
+            }
+        }
+    }
+
+    // The selected dialogue as the game will show it
+    fn display_preview(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Preview");
+            if ui.button("Reload Images").clicked() {
+                self.images.clear();
+            }
+        });
+
+        let Some(tab) = self.tabs.get(self.active) else {
+            return;
+        };
+        let Some(dialogue) = tab.selected_dialogue.as_ref().and_then(|id| tab.dialogues.get(id)) else {
+            ui.weak("Select a dialogue to preview it.");
+            return;
+        };
+        let issues = dialogue_issues(dialogue, &tab.dialogues, &self.conversation_map(), &self.speakers);
+        let background = self.project.as_ref().and_then(|project| {
+            let location = project.location_of(tab.conversation.as_ref()?)?;
+            project.locations[location].background.as_deref()
+        });
+
+        egui::ScrollArea::vertical().id_salt("preview_scroll_area").show(ui, |ui| {
+            // Variables are left as {name}, since there's no player to fill them in from,
+            // and variants show the text for a player who has nothing yet
+            let context = MarkupContext {
+                speakers: &self.speakers,
+                variables: &|_| None,
+                has: &|_| false,
+            };
+            show_dialogue(ui, &mut self.images, &mut self.markup, dialogue, background, &context, false);
+
+            ui.separator();
+            match dialogue.completeness() {
+                Completeness::Stub => {
+                    ui.colored_label(ui.visuals().warn_fg_color, "Needs writing: still a stub");
+                }
+                Completeness::Incomplete(missing) => {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("Needs writing: {}", missing.join(", ")));
+                }
+                Completeness::Complete => {}
+            }
+            if issues.is_empty() {
+                ui.weak("No problems found.");
+            }
+            for issue in issues {
+                ui.colored_label(ui.visuals().error_fg_color, issue);
+            }
+        });
+    }
+
+    fn display_transfer(&mut self, ctx: &egui::Context) {
+        let Some(transfer) = &mut self.transfer else {
+            return;
+        };
+
+        let mut copy = false;
+        let mut remove_source = false;
+        let mut cancel = false;
+        egui::Window::new(format!("Copy or Move {}", transfer.id))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let target_name = |tab: &FileTab| tab.conversation.clone().unwrap_or_else(|| tab.file_path.clone());
+                egui::ComboBox::from_label("To file")
+                    .selected_text(transfer.target.map(|index| target_name(&self.tabs[index])).unwrap_or_default())
+                    .show_ui(ui, |ui| {
+                        for (index, tab) in self.tabs.iter().enumerate() {
+                            if index != self.active {
+                                ui.selectable_value(&mut transfer.target, Some(index), target_name(tab));
+                            }
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(transfer.target.is_some(), egui::Button::new("Copy")).clicked() {
+                        copy = true;
+                    }
+                    // Links can only follow the dialogue into a file the project names as a conversation
+                    let movable = transfer.target.is_some_and(|index| self.tabs[index].conversation.is_some());
+                    if ui
+                        .add_enabled(movable, egui::Button::new("Move"))
+                        .on_hover_text("Links to it in this file are pointed at the new file")
+                        .on_disabled_hover_text("Links can't point into a file that isn't a conversation of the project; copy it instead")
+                        .clicked()
+                    {
+                        copy = true;
+                        remove_source = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if cancel {
+            self.transfer = None;
+        }
+        if !copy {
+            return;
+        }
+        let Some(Transfer { id, target: Some(target) }) = self.transfer.take() else {
+            return;
+        };
+
+        let source = self.active;
+        let source_conversation = self.tabs[source].conversation.clone();
+        let target_conversation = self.tabs[target].conversation.clone();
+        let Some(mut dialogue) = self.tabs[source].dialogues.get(&id).cloned() else {
+            return;
+        };
+
+        // Links that only made sense inside the source file now have to name it
+        if let Some(source_conversation) = &source_conversation {
+            let target_dialogues = &self.tabs[target].dialogues;
+            for option in dialogue.options.iter_mut() {
+                for link in [&mut option.success_dialogue, &mut option.failure_dialogue].into_iter().flatten() {
+                    if split_link(link).0.is_none() && !target_dialogues.contains_key(link.as_str()) {
+                        *link = format!("{}/{}", source_conversation, link);
+                    }
+                }
+            }
+        }
+
+        if let Err(err) = self.tabs[target].insert_dialogue(&id, dialogue) {
+            self.tabs[source].status = Some(err.to_string());
+            return;
+        }
+
+        // Without a conversation to point the links at, the original has to stay for them
+        match target_conversation.filter(|_| remove_source) {
+            Some(conversation) => self.delete_dialogue(&id, Some(format!("{}/{}", conversation, id))),
+            None => self.tabs[source].status = Some(format!("Copied {} to {}", id, self.tabs[target].file_path)),
+        }
+    }
+
+    fn display_locations(&mut self, ctx: &egui::Context) {
+        if !self.locations.open {
+            return;
+        }
+        let Some(project) = &mut self.project else {
+            return;
+        };
+
+        let mut open = true;
+        let mut action = None;
+        let mut save = false;
+        let editor = &mut self.locations;
+        let location_names = project.location_names();
+        egui::Window::new("Locations").open(&mut open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Start location:");
+                egui::ComboBox::from_id_salt("start_location")
+                    .selected_text(project.start_location.as_str())
+                    .show_ui(ui, |ui| {
+                        for name in &location_names {
+                            ui.selectable_value(&mut project.start_location, name.clone(), name.as_str());
+                        }
+                    });
+            });
+
+            ui.horizontal_wrapped(|ui| {
+                for name in &location_names {
+                    if ui.selectable_label(editor.selected.as_ref() == Some(name), name).clicked() {
+                        editor.temp_name = name.clone();
+                        editor.selected = Some(name.clone());
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut editor.new_location);
+                if ui.button("New Location").clicked() {
+                    let name = editor.new_location.trim().to_string();
+                    match project.add_location(&name) {
+                        Ok(()) => {
+                            editor.temp_name = name.clone();
+                            editor.selected = Some(name);
+                            editor.new_location.clear();
+                        }
+                        Err(err) => editor.status = Some(err.to_string()),
+                    }
+                }
+            });
+
+            ui.separator();
+            if let Some(name) = editor.selected.clone() {
+                if let Some(location) = project.locations.get_mut(&name) {
+                    ui.heading(format!("Editing Location: {}", name));
+                    action = edit_location(ui, &name, location, &mut editor.temp_name, &location_names)
+                        .map(|action| (name, action));
+                }
+            }
+
+            ui.separator();
+            let problems = project.validate(&self.project_path);
+            if problems.is_empty() {
+                ui.label("No problems found.");
+            }
+            for problem in &problems {
+                ui.colored_label(egui::Color32::RED, problem.to_string());
+            }
+
+            if ui.button("Save Project").clicked() {
+                save = true;
+            }
+            if let Some(status) = &editor.status {
+                ui.label(status);
+            }
+        });
+
+        match action {
+            Some((old_name, LocationEditAction::Rename(new_name))) => match project.rename_location(&old_name, &new_name) {
+                Ok(()) => {
+                    editor.selected = Some(new_name.clone());
+                    editor.status = Some(format!("Renamed {} to {}", old_name, new_name));
+                }
+                Err(err) => editor.status = Some(err.to_string()),
+            },
+            Some((name, LocationEditAction::Remove)) => {
+                project.remove_location(&name);
+                editor.selected = None;
+                editor.status = Some(format!("Deleted {}", name));
+            }
+            Some((_, LocationEditAction::OpenConversation(conversation))) => {
+                let path = project
+                    .conversation_files(&self.project_path)
+                    .into_iter()
+                    .find(|file| file.conversation == conversation)
+                    .map(|file| file.path);
+                if let Some(path) = path {
+                    self.open_file(&path);
+                }
+            }
+            None => {}
+        }
+
+        if save {
+            self.save_project();
+        }
+        if !open {
+            self.locations.open = false;
+        }
+    }
+
+    fn display_speakers(&mut self, ctx: &egui::Context) {
+        if !self.speaker_editor.open {
+            return;
+        }
+
+        let mut open = true;
+        let mut remove = None;
+        let mut save = false;
+        let editor = &mut self.speaker_editor;
+        egui::Window::new("Speakers").open(&mut open).vscroll(true).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.text_edit_singleline(&mut editor.new_speaker);
+                if ui.button("New Speaker").clicked() {
+                    let id = editor.new_speaker.trim().to_string();
+                    if id.is_empty() {
+                        editor.status = Some("Speaker ID cannot be empty".to_string());
+                    } else if self.speakers.contains_key(&id) {
+                        editor.status = Some(format!("A speaker with ID '{}' already exists", id));
+                    } else {
+                        let speaker = Speaker {
+                            name: id.clone(),
+                            ..Default::default()
+                        };
+                        self.speakers.insert(id, speaker);
+                        editor.new_speaker.clear();
+                    }
+                }
+            });
+
+            for (id, speaker) in self.speakers.iter_mut() {
+                egui::CollapsingHeader::new(id.as_str()).show(ui, |ui| {
+                    if edit_speaker(ui, id, speaker) {
+                        remove = Some(id.clone());
+                    }
+                });
+            }
+
+            if ui.button("Save Speakers").clicked() {
+                save = true;
+            }
+            if let Some(status) = &editor.status {
+                ui.label(status);
+            }
+        });
+
+        if let Some(id) = remove {
+            self.speakers.shift_remove(&id);
+        }
+        if save {
+            self.save_speakers();
+        }
+        if !open {
+            self.speaker_editor.open = false;
+        }
+    }
+
+    fn display_close_prompt(&mut self, ctx: &egui::Context) {
+        if !self.closing {
+            return;
+        }
+
+        let mut dirty: Vec<String> = self.tabs.iter().filter(|tab| tab.is_dirty()).map(|tab| tab.file_path.clone()).collect();
+        if self.project_dirty() {
+            dirty.push(self.project_path.clone());
+        }
+        if self.speakers_dirty() {
+            dirty.push(speaker_path(&self.project_path));
+        }
+        let mut close = false;
+        egui::Window::new("Unsaved Changes").collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label("These files have unsaved changes:");
+            for file_path in &dirty {
+                ui.label(file_path);
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Save All and Close").clicked() {
+                    close = self.tabs.iter_mut().filter(|tab| tab.is_dirty()).all(|tab| tab.save());
+                    close = close && (!self.project_dirty() || self.save_project());
+                    close = close && (!self.speakers_dirty() || self.save_speakers());
+                }
+                if ui.button("Discard and Close").clicked() {
+                    for tab in &mut self.tabs {
+                        tab.discard_recovery();
+                    }
+                    close = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    self.closing = false;
+                }
+            });
+        });
+
+        if close {
+            self.allow_close = true;
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+
+    // Saved conversations feed the link checks of every other tab
+    fn refresh_disk_conversation(&mut self, index: usize) {
+        if let Some(conversation) = &self.tabs[index].conversation {
+            self.disk_conversations.insert(conversation.clone(), self.tabs[index].saved.clone());
+        }
+    }
+}
+
+impl eframe::App for DialogueEditorApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.handle_shortcuts(ctx);
+        self.watch_files(ctx);
+
+        egui::SidePanel::left("project_panel").show(ctx, |ui| {
+            self.display_project_panel(ui);
+        });
+
+        if self.show_preview {
+            egui::SidePanel::right("preview_panel").default_width(360.0).show(ctx, |ui| {
+                self.display_preview(ui);
+            });
+        }
+
+        egui::SidePanel::right("history_panel").show(ctx, |ui| {
+            if let Some(tab) = self.tabs.get_mut(self.active) {
+                tab.display_history(ui);
+            }
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Dialogue Editor");
+            self.display_tabs(ui);
+
+            let Some(tab) = self.tabs.get_mut(self.active) else {
+                ui.label("Open a conversation from the project panel.");
+                return;
+            };
+
+            // Add buttons for creating and saving dialogues
+            let mut saved = false;
+            ui.horizontal(|ui| {
+                if ui.button("New Dialogue").clicked() {
+                    tab.create_dialogue();
+                }
+                let save = ui
+                    .add_enabled(!tab.has_load_errors(), egui::Button::new("Save"))
+                    .on_disabled_hover_text("Saving would drop what couldn't be read from the file");
+                if save.clicked() {
+                    saved = tab.save();
+                }
+                if ui.add_enabled(tab.history.can_undo() || tab.pending_edit.is_some(), egui::Button::new("Undo")).clicked() {
+                    tab.undo();
+                }
+                if ui.add_enabled(tab.history.can_redo(), egui::Button::new("Redo")).clicked() {
+                    tab.redo();
+                }
+                ui.checkbox(&mut self.show_preview, "Preview");
+            });
+
+            if tab.has_load_errors() {
+                ui.colored_label(ui.visuals().error_fg_color, "Can't be saved: part of this file couldn't be read, and saving would lose that part");
+            } else if !tab.load_issues.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(ui.visuals().warn_fg_color, "Saving drops these unknown fields");
+                    if ui.button("Save Anyway").clicked() {
+                        saved = tab.save_dropping_unknown_fields();
+                    }
+                });
+            }
+            for issue in &tab.load_issues {
+                ui.colored_label(ui.visuals().warn_fg_color, issue.to_string());
+            }
+            if let Some(status) = &tab.status {
+                ui.label(status);
+            }
+            if saved {
+                self.refresh_disk_conversation(self.active);
+            }
+
+            // Use ScrollArea to wrap the rest of the content
+            egui::ScrollArea::vertical()
+                .id_salt("dialogue_scroll_area") // Provide a unique identifier for the scroll area
+                .show(ui, |ui| {
+                    // Display list of dialogues
+                    self.display_dialogue_list(ui);
+
+                    // Edit the selected dialogue
+                    self.display_editor(ui, ctx);
+                });
+        });
+
+        self.confirm_delete(ctx);
+        self.display_usages(ctx);
+        self.display_transfer(ctx);
+        self.display_locations(ctx);
+        self.display_speakers(ctx);
+
+        let mut close = None;
+        for (index, tab) in self.tabs.iter_mut().enumerate() {
+            if tab.display_prompt(ctx) {
+                close = Some(index);
+            }
+        }
+        if let Some(index) = close {
+            self.close_tab(index);
+        }
+        self.display_close_prompt(ctx);
+    }
+}
+
+// Reads a dialogue file for editing, with the problems that kept part of it out; a file that doesn't exist yet is empty
+fn read_dialogue_file(file_path: &str) -> (Dialogues, Vec<LoadIssue>) {
+    match load_dialogue_file(file_path, Strictness::Lenient) {
+        Ok(loaded) => (loaded.dialogues, loaded.issues),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (Dialogues::new(), vec![]),
+        Err(err) => {
+            let issue = LoadIssue {
+                severity: Severity::Error,
+                dialogue: file_path.to_string(),
+                field: None,
+                message: err.to_string(),
+            };
+            (Dialogues::new(), vec![issue])
+        }
+    }
+}
+
+impl FileTab {
+    fn open(file_path: &str, conversation: Option<String>) -> Self {
+        let (dialogues, load_issues) = read_dialogue_file(file_path);
+
+        // Offer back whatever the last session autosaved but never saved
+        let prompt = std::fs::read_to_string(recovery_path(file_path))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Dialogues>(&content).ok())
+            .filter(|recovered| *recovered != dialogues)
+            .map(Prompt::Recovery);
+
+        Self {
+            file_path: file_path.to_string(),
+            conversation,
+            saved: dialogues.clone(),
+            dialogues,
+            disk_modified: modified_time(file_path),
+            autosaved: None,
+            prompt,
+            selected_dialogue: None,
+            temp_id: String::new(),
+            status: None,
+            pending_delete: None,
+            usages_of: None,
+            history: EditHistory::new(),
+            pending_edit: None,
+            load_issues,
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dialogues != self.saved
+    }
+
+    // Dialogues the loader left out would be lost for good, so a file with load errors can't be saved
+    fn has_load_errors(&self) -> bool {
+        self.load_issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+
+    fn save(&mut self) -> bool {
+        self.commit_pending_edit();
+        if self.has_load_errors() {
+            self.status = Some(format!("Not saved: fix the errors in {} by hand, it reloads once it changes", self.file_path));
+            return false;
+        }
+        // The only other issues are unknown fields, which saving drops, so that takes a confirm
+        if !self.load_issues.is_empty() {
+            self.status = Some("Not saved: saving drops the unknown fields listed above, use Save Anyway for that".to_string());
+            return false;
+        }
+        match save_to_file(&self.dialogues, self.file_path.clone()) {
+            Ok(()) => {
+                self.saved = self.dialogues.clone();
+                self.disk_modified = modified_time(&self.file_path);
+                self.discard_recovery();
+                self.status = Some("Saved".to_string());
+                true
+            }
+            Err(err) => {
+                self.status = Some(format!("Save failed: {}", err));
+                false
+            }
+        }
+    }
+
+    // Saves without the unknown fields the loader warned about
+    fn save_dropping_unknown_fields(&mut self) -> bool {
+        if self.has_load_errors() {
+            return self.save();
+        }
+        let issues = std::mem::take(&mut self.load_issues);
+        let saved = self.save();
+        if !saved {
+            self.load_issues = issues;
+        }
+        saved
+    }
+
+    fn discard_recovery(&mut self) {
+        let _ = std::fs::remove_file(recovery_path(&self.file_path));
+        self.autosaved = None;
+    }
+
+    fn autosave(&mut self) {
+        if !self.is_dirty() || self.autosaved.as_ref() == Some(&self.dialogues) {
+            return;
+        }
+        match save_recovery(&self.dialogues, &self.file_path) {
+            Ok(()) => self.autosaved = Some(self.dialogues.clone()),
+            Err(err) => self.status = Some(format!("Autosave failed: {}", err)),
+        }
+    }
+
+    fn check_disk(&mut self) {
+        if self.prompt.is_some() || modified_time(&self.file_path) == self.disk_modified {
+            return;
+        }
+        if self.is_dirty() {
+            self.prompt = Some(Prompt::ChangedOnDisk);
+        } else {
+            // Nothing of ours to lose, just pick up the new version
+            self.dialogues = self.load_from_disk();
+            self.saved = self.dialogues.clone();
+            self.clear_stale_selection();
+            self.status = Some(format!("Reloaded {}, it changed on disk", self.file_path));
+        }
+    }
+
+    // Replaces the editor's contents with the file on disk; the old history no longer applies
+    fn load_from_disk(&mut self) -> Dialogues {
+        let (dialogues, load_issues) = read_dialogue_file(&self.file_path);
+        self.load_issues = load_issues;
+        self.disk_modified = modified_time(&self.file_path);
+        self.pending_edit = None;
+        self.history = EditHistory::new();
+        dialogues
+    }
+
+    fn clear_stale_selection(&mut self) {
+        if let Some(id) = &self.selected_dialogue {
+            if !self.dialogues.contains_key(id) {
+                self.select(None);
+            }
+        }
+    }
+
+    // Returns true once the user has agreed to close this tab
+    fn display_prompt(&mut self, ctx: &egui::Context) -> bool {
+        let Some(prompt) = &self.prompt else {
+            return false;
+        };
+
+        let mut answered = false;
+        let mut close = false;
+        let window_id = egui::Id::new(("file_prompt", &self.file_path));
+        match prompt {
+            Prompt::CloseTab => {
+                egui::Window::new("Unsaved Changes").id(window_id).collapsible(false).resizable(false).show(ctx, |ui| {
+                    ui.label(format!("{} has unsaved changes.", self.file_path));
+                    ui.horizontal(|ui| {
+                        if ui.button("Save and Close").clicked() && self.save() {
+                            close = true;
+                        }
+                        if ui.button("Discard and Close").clicked() {
+                            self.discard_recovery();
+                            close = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            answered = true;
+                        }
+                    });
+                });
+            }
+            Prompt::ChangedOnDisk => {
+                egui::Window::new("File Changed on Disk").id(window_id).collapsible(false).resizable(false).show(ctx, |ui| {
+                    ui.label(format!("{} was modified outside the editor while you had unsaved changes.", self.file_path));
+                    ui.horizontal(|ui| {
+                        if ui.button("Reload").on_hover_text("Discard your changes and load the file").clicked() {
+                            self.dialogues = self.load_from_disk();
+                            self.saved = self.dialogues.clone();
+                            self.status = Some("Reloaded from disk, your changes were discarded".to_string());
+                            answered = true;
+                        }
+                        if ui.button("Merge").on_hover_text("Load the file and reapply the dialogues you changed").clicked() {
+                            let theirs = self.load_from_disk();
+                            let (merged, conflicts) = merge_dialogues(&self.saved, &self.dialogues, &theirs);
+                            self.dialogues = merged;
+                            self.saved = theirs;
+                            self.status = Some(if conflicts.is_empty() {
+                                "Merged changes from disk".to_string()
+                            } else {
+                                format!("Merged changes from disk, kept your version of: {}", conflicts.join(", "))
+                            });
+                            answered = true;
+                        }
+                        if ui.button("Keep Mine").on_hover_text("Ignore the file; saving will overwrite it").clicked() {
+                            self.disk_modified = modified_time(&self.file_path);
+                            answered = true;
+                        }
+                    });
+                });
+                if answered {
+                    self.clear_stale_selection();
+                }
+            }
+            Prompt::Recovery(recovered) => {
+                let recovered = recovered.clone();
+                egui::Window::new("Recover Autosave").id(window_id).collapsible(false).resizable(false).show(ctx, |ui| {
+                    ui.label(format!("Unsaved changes to {} were autosaved in a previous session.", self.file_path));
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            self.dialogues = recovered;
+                            self.status = Some("Restored autosaved changes".to_string());
+                            answered = true;
+                        }
+                        if ui.button("Discard").clicked() {
+                            self.discard_recovery();
+                            answered = true;
+                        }
+                    });
+                });
+            }
+        }
+
+        if answered || close {
+            self.prompt = None;
+        }
+        close
+    }
+
+    // Returns a rename the user asked for, which the app carries out since it can touch other files
+    fn display_editor(&mut self, ui: &mut egui::Ui, ctx: &egui::Context, catalogue: &EditorCatalogue) -> Option<(String, String)> {
+        // Close the running field edit once its widget loses focus or another dialogue is selected
+        let active_widget = ctx.memory(|memory| memory.focused()).or(ctx.dragged_id());
+        if let Some(pending) = &self.pending_edit {
+            if Some(pending.widget) != active_widget || self.selected_dialogue.as_ref() != Some(&pending.id) {
+                self.commit_pending_edit();
+            }
+        }
+
+        let mut action = None;
+        let mut edited = None;
+        if let Some(selected_id) = &self.selected_dialogue {
+            if let Some(dialogue) = self.dialogues.get_mut(selected_id) {
+                if self.temp_id.is_empty() {
+                    self.temp_id = selected_id.clone();
+                }
+                let before = dialogue.clone();
+                action = edit_dialogue(ui, selected_id, dialogue, &mut self.temp_id, catalogue)
+                    .map(|action| (selected_id.clone(), action));
+                if *dialogue != before {
+                    edited = Some((selected_id.clone(), before));
+                }
+            }
+        }
+
+        match action {
+            Some((old_id, DialogueEditAction::Rename(new_id))) => {
+                if let Some((id, before)) = edited {
+                    self.track_edit(ctx, id, before);
+                }
+                return Some((old_id, new_id));
+            }
+            // Create & Link sets the link in the same click, so both go into one undo step
+            Some((linking_id, DialogueEditAction::CreateDialogue(id))) => {
+                self.create_linked_dialogue(&linking_id, edited.map(|(_, before)| before), &id)
+            }
+            None => {
+                if let Some((id, before)) = edited {
+                    self.track_edit(ctx, id, before);
+                }
+            }
+        }
+        None
+    }
+
+    fn create_dialogue(&mut self) {
+        self.commit_pending_edit();
+        let id = create_dialogue(&mut self.dialogues); // Use the function from lib.rs
+        self.history.record(EditCommand::CreateDialogue {
+            id: id.clone(),
+            dialogue: self.dialogues[&id].clone(),
+        });
+        self.select(Some(id));
+    }
+
+    // Creates the dialogue an option was just linked to, leaving the linking dialogue selected
+    // before is the linking dialogue from before this frame's change to it, if there was one
+    fn create_linked_dialogue(&mut self, linking_id: &str, before: Option<Dialogue>, id: &str) {
+        // A field edit still open on the linking dialogue goes into the same step as well
+        let before = match self.pending_edit.take() {
+            Some(pending) if pending.id == linking_id => Some(pending.before),
+            pending => {
+                self.pending_edit = pending;
+                self.commit_pending_edit();
+                before
+            }
+        };
+        let link_edit = before.map(|before| EditCommand::EditDialogue {
+            id: linking_id.to_string(),
+            before,
+            after: self.dialogues[linking_id].clone(),
+        });
+
+        match create_dialogue_with_id(&mut self.dialogues, id) {
+            Ok(()) => {
+                let create = EditCommand::CreateDialogue {
+                    id: id.to_string(),
+                    dialogue: self.dialogues[id].clone(),
+                };
+                self.history.record(match link_edit {
+                    Some(link_edit) => EditCommand::Group {
+                        label: format!("Create and link {}", id),
+                        commands: vec![link_edit, create],
+                    },
+                    None => create,
+                });
+                self.status = Some(format!("Created {}", id));
+            }
+            Err(err) => {
+                if let Some(link_edit) = link_edit {
+                    self.history.record(link_edit);
+                }
+                self.status = Some(err.to_string());
+            }
+        }
+    }
+
+    // Adds a dialogue copied or moved in from another file
+    fn insert_dialogue(&mut self, id: &str, dialogue: Dialogue) -> Result<(), RenameError> {
+        self.commit_pending_edit();
+        if self.dialogues.contains_key(id) {
+            return Err(RenameError::Collision(id.to_string()));
+        }
+        self.dialogues.insert(id.to_string(), dialogue.clone());
+        self.history.record(EditCommand::CreateDialogue {
+            id: id.to_string(),
+            dialogue,
+        });
+        self.status = Some(format!("Added {}", id));
+        Ok(())
+    }
+
+    fn select(&mut self, id: Option<String>) {
+        self.temp_id = id.clone().unwrap_or_default();
+        self.selected_dialogue = id;
+    }
+
+    fn track_edit(&mut self, ctx: &egui::Context, id: String, before: Dialogue) {
+        if self.pending_edit.is_some() {
+            return; // Still typing in the same widget, keep the original snapshot
+        }
+
+        match ctx.memory(|memory| memory.focused()).or(ctx.dragged_id()) {
+            Some(widget) => self.pending_edit = Some(PendingEdit { id, before, widget }),
+            None => {
+                // Buttons and checkboxes change the dialogue in a single click
+                let after = self.dialogues[&id].clone();
+                self.history.record(EditCommand::EditDialogue { id, before, after });
+            }
+        }
+    }
+
+    fn commit_pending_edit(&mut self) {
+        let Some(pending) = self.pending_edit.take() else {
+            return;
+        };
+        if let Some(after) = self.dialogues.get(&pending.id) {
+            if *after != pending.before {
+                self.history.record(EditCommand::EditDialogue {
+                    id: pending.id,
+                    before: pending.before,
+                    after: after.clone(),
+                });
+            }
+        }
+    }
+
+    fn undo(&mut self) {
+        self.commit_pending_edit();
+        if let Some(command) = self.history.undo(&mut self.dialogues) {
+            self.status = Some(format!("Undid: {}", command.label()));
+            let selected = command.selected_after(true).map(str::to_string);
+            self.select(selected);
+        }
+    }
+
+    fn redo(&mut self) {
+        self.commit_pending_edit();
+        if let Some(command) = self.history.redo(&mut self.dialogues) {
+            self.status = Some(format!("Redid: {}", command.label()));
+            let selected = command.selected_after(false).map(str::to_string);
+            self.select(selected);
+        }
+    }
+
+    fn display_history(&mut self, ui: &mut egui::Ui) {
+        ui.heading("History");
+
+        let mut target = None;
+        egui::ScrollArea::vertical()
+            .id_salt("history_scroll_area")
+            .show(ui, |ui| {
+                let done = self.history.done();
+                if ui.selectable_label(done.is_empty() && self.pending_edit.is_none(), "(opened file)").clicked() {
+                    target = Some(0);
+                }
+                for (i, command) in done.iter().enumerate() {
+                    let current = i + 1 == done.len() && self.pending_edit.is_none();
+                    if ui.selectable_label(current, command.label()).clicked() {
+                        target = Some(i + 1);
+                    }
+                }
+                if let Some(pending) = &self.pending_edit {
+                    let _ = ui.selectable_label(true, format!("Edit {} (in progress)", pending.id));
+                }
+                for (i, command) in self.history.undone().enumerate() {
+                    let label = egui::Label::new(egui::RichText::new(command.label()).weak()).sense(egui::Sense::click());
+                    if ui.add(label).clicked() {
+                        target = Some(done.len() + i + 1);
+                    }
+                }
+            });
+
+        // Step through the history until the clicked entry is the latest applied command
+        if let Some(target) = target {
+            self.commit_pending_edit();
+            while self.history.done().len() > target && self.history.can_undo() {
+                self.undo();
+            }
+            while self.history.done().len() < target && self.history.can_redo() {
+                self.redo();
+            }
+        }
+    }
+
+    // Renames within this file; with the conversation known, "Conversation/Old" links here and in the
+    // other tabs are rewritten too, each tab recording its part in its own history
+    fn rename_dialogue(&mut self, old_id: &str, new_id: &str, conversation: Option<&str>, others: &mut [&mut FileTab]) {
+        self.commit_pending_edit();
+        let references = find_references(&self.dialogues, old_id);
+        let links = conversation.map(|conversation| (format!("{}/{}", conversation, old_id), format!("{}/{}", conversation, new_id)));
+        let linking = |dialogues: &Dialogues| links.as_ref().map(|(old_link, _)| find_references(dialogues, old_link)).unwrap_or_default();
+        let own_links = linking(&self.dialogues);
+        let other_links: Vec<Vec<DialogueReference>> = others.iter().map(|tab| linking(&tab.dialogues)).collect();
+        for tab in others.iter_mut() {
+            tab.commit_pending_edit();
+        }
+
+        let result = match conversation {
+            Some(conversation) => {
+                let mut files: Vec<&mut Dialogues> = others.iter_mut().map(|tab| &mut tab.dialogues).collect();
+                rename_dialogue_in_project(&mut self.dialogues, conversation, &mut files, old_id, new_id)
+            }
+            None => rename_dialogue(&mut self.dialogues, old_id, new_id),
+        };
+        let rewritten = match result {
+            Ok(rewritten) => rewritten,
+            Err(err) => {
+                self.status = Some(err.to_string());
+                return;
+            }
+        };
+
+        if old_id != new_id {
+            let rename = EditCommand::RenameDialogue {
+                old_id: old_id.to_string(),
+                new_id: new_id.to_string(),
+                references,
+            };
+            let command = match &links {
+                Some((old_link, new_link)) if !own_links.is_empty() => EditCommand::Group {
+                    label: rename.label(),
+                    commands: vec![
+                        rename,
+                        EditCommand::RewriteLinks {
+                            from: old_link.clone(),
+                            to: Some(new_link.clone()),
+                            references: own_links,
+                        },
+                    ],
+                },
+                _ => rename,
+            };
+            self.history.record(command);
+        }
+
+        let mut files = 0;
+        if let Some((old_link, new_link)) = &links {
+            for (tab, references) in others.iter_mut().zip(other_links) {
+                if references.is_empty() {
+                    continue;
+                }
+                files += 1;
+                tab.status = Some(format!("{} link(s) to {} now point at {}", references.len(), old_link, new_link));
+                tab.history.record(EditCommand::RewriteLinks {
+                    from: old_link.clone(),
+                    to: Some(new_link.clone()),
+                    references,
+                });
+            }
+        }
+        self.select(Some(new_id.to_string()));
+        self.status = Some(format!(
+            "Renamed {} to {}, updated {} reference(s) in {} other file(s)",
+            old_id, new_id, rewritten, files
+        ));
+    }
+
+    // The file order with one dialogue moved to just before or just after another
+    fn moved_order(&self, id: &str, next_to: &str, after: bool) -> Vec<String> {
+        let mut order: Vec<String> = self.dialogues.keys().filter(|key| *key != id).cloned().collect();
+        let index = order.iter().position(|key| key == next_to).unwrap_or_default() + usize::from(after);
+        order.insert(index, id.to_string());
+        order
+    }
+
+    fn reorder_dialogues(&mut self, after: Vec<String>) {
+        self.commit_pending_edit();
+        let before: Vec<String> = self.dialogues.keys().cloned().collect();
+        if before == after {
+            return;
+        }
+        reorder_dialogues(&mut self.dialogues, &after);
+        self.history.record(EditCommand::ReorderDialogues { before, after });
+    }
+
+    // Returns the dialogue to delete and where its links should point once the user confirms
+    fn confirm_delete(&mut self, ctx: &egui::Context, usages: &[Usage]) -> Option<(String, Option<String>)> {
+        let pending = self.pending_delete.as_mut()?;
+
+        let mut other_ids: Vec<&String> = self.dialogues.keys().filter(|id| **id != pending.id).collect();
+        other_ids.sort();
+
+        let mut choice = None;
+        egui::Window::new("Delete Dialogue")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("Delete {}?", pending.id));
+
+                if usages.is_empty() {
+                    ui.label("No options link to this dialogue.");
+                } else {
+                    ui.label(format!("{} option(s) link to this dialogue:", usages.len()));
+                    for usage in usages {
+                        ui.label(format!(
+                            "{} option {} ({:?}): {}",
+                            usage.source(),
+                            usage.reference.option_index + 1,
+                            usage.reference.kind,
+                            usage.description
+                        ));
+                    }
+
+                    egui::ComboBox::from_label("Redirect to")
+                        .selected_text(pending.redirect_to.as_str())
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut pending.redirect_to, String::new(), "(clear links)");
+                            for id in &other_ids {
+                                ui.selectable_value(&mut pending.redirect_to, (*id).clone(), id.as_str());
+                            }
+                        });
+                }
+
+                ui.horizontal(|ui| {
+                    if pending.redirect_to.is_empty() {
+                        if ui.button("Delete").clicked() {
+                            choice = Some(DeleteChoice::Clear);
+                        }
+                    } else if ui.button("Delete and Redirect").clicked() {
+                        choice = Some(DeleteChoice::Redirect(pending.redirect_to.clone()));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        choice = Some(DeleteChoice::Cancel);
+                    }
+                });
+            });
+
+        // The window stays up until the user picks something
+        let choice = choice?;
+        let id = self.pending_delete.take().map(|pending| pending.id).unwrap_or_default();
+        match choice {
+            DeleteChoice::Redirect(target) => Some((id, Some(target))),
+            DeleteChoice::Clear => Some((id, None)),
+            DeleteChoice::Cancel => None,
+        }
+    }
+
+    // The file's own "Conversation/ID" links to the dialogue go the same way as its plain ones, in the same step
+    // Returns whether there was a dialogue to delete
+    fn delete_dialogue(&mut self, id: &str, redirect_to: Option<String>) -> bool {
+        self.commit_pending_edit();
+        let link = self.conversation.as_ref().map(|conversation| format!("{}/{}", conversation, id));
+        let own_links: Vec<DialogueReference> = link
+            .as_ref()
+            .map(|link| find_references(&self.dialogues, link))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|reference| reference.dialogue_id != id)
+            .collect();
+
+        let Some(deleted) = delete_dialogue(&mut self.dialogues, id, redirect_to.as_deref()) else {
+            return false;
+        };
+        self.status = Some(match &redirect_to {
+            Some(target) => format!("Deleted {}, redirected {} link(s) to {}", id, deleted.references.len() + own_links.len(), target),
+            None => format!("Deleted {}, cleared {} link(s)", id, deleted.references.len() + own_links.len()),
+        });
+        if self.selected_dialogue.as_deref() == Some(id) {
+            self.select(None);
+        }
+
+        let delete = EditCommand::DeleteDialogue {
+            deleted,
+            redirect_to: redirect_to.clone(),
+        };
+        match link {
+            Some(link) if !own_links.is_empty() => {
+                let rewrite = EditCommand::RewriteLinks {
+                    from: link,
+                    to: redirect_to.map(|target| qualified(self.conversation.as_deref().unwrap_or_default(), &target)),
+                    references: own_links,
+                };
+                rewrite.apply(&mut self.dialogues);
+                self.history.record(EditCommand::Group {
+                    label: delete.label(),
+                    commands: vec![delete, rewrite],
+                });
+            }
+            _ => self.history.record(delete),
+        }
+        true
+    }
+
+    // Points this file's links to a target somewhere else, or clears them, as one undo step
+    // Returns how many links changed
+    fn rewrite_links(&mut self, from: &str, to: Option<&str>) -> usize {
+        let references = find_references(&self.dialogues, from);
+        if references.is_empty() {
+            return 0;
+        }
+        self.commit_pending_edit();
+        let count = references.len();
+        self.status = Some(match to {
+            Some(to) => format!("{} link(s) to {} now point at {}", count, from, to),
+            None => format!("Cleared {} link(s) to {}", count, from),
+        });
+        self.history.apply(
+            EditCommand::RewriteLinks {
+                from: from.to_string(),
+                to: to.map(str::to_string),
+                references,
+            },
+            &mut self.dialogues,
+        );
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_file_with_load_issues_is_not_saved() {
+        let path = std::env::temp_dir().join(format!("editor_load_issues_{}.json", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let content = r#"{ "Start": { "intro": "Hi", "sucess_dialogue": "Next" }, "Broken": { "intro": 3 } }"#;
+        std::fs::write(&path, content).unwrap();
+
+        let mut tab = FileTab::open(&path, None);
+        assert_eq!(tab.load_issues.len(), 2);
+        assert!(!tab.dialogues.contains_key("Broken"));
+        tab.dialogues["Start"].intro = "Hello".to_string();
+        assert!(!tab.save());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unknown_fields_are_dropped_only_when_saving_anyway() {
+        let path = std::env::temp_dir().join(format!("editor_unknown_fields_{}.json", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let content = r#"{ "Start": { "intro": "Hi", "sucess_dialogue": "Next" } }"#;
+        std::fs::write(&path, content).unwrap();
+
+        let mut tab = FileTab::open(&path, None);
+        assert_eq!(tab.load_issues[0].severity, Severity::Warning);
+        assert!(!tab.save());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+
+        assert!(tab.save_dropping_unknown_fields());
+        assert!(tab.load_issues.is_empty());
+        let (dialogues, issues) = read_dialogue_file(&path);
+        assert!(issues.is_empty());
+        assert_eq!(dialogues["Start"].intro, "Hi");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn a_pending_delete_waits_for_a_click() {
+        let mut tab = FileTab::open("missing/file.json", None);
+        tab.dialogues.insert("Start".to_string(), Dialogue::default());
+        tab.pending_delete = Some(PendingDelete {
+            id: "Start".to_string(),
+            redirect_to: String::new(),
+        });
+
+        let ctx = egui::Context::default();
+        let mut choice = None;
+        let _ = ctx.run(egui::RawInput::default(), |ctx| choice = tab.confirm_delete(ctx, &[]));
+        assert_eq!(choice, None);
+        assert_eq!(tab.pending_delete.map(|pending| pending.id).as_deref(), Some("Start"));
+    }
+}
@@ -0,0 +1,415 @@
+// Plays a conversation every way it can go, to find its endings and the places a player can get stuck
+// Unlike the link checks in analysis.rs this follows the real rules: options hidden behind visible_when,
+// flags and items picked up along the way, and both outcomes of every challenge a build can pass or fail.
+// Where an option leads is Option::outcome, the same rule the game moves by, and the dialogues should be
+// loaded the way the game loads them (Project::load_game_conversations), defaults included
+//
+// A state is the dialogue plus the flags and items held, so the same dialogue reached with different
+// flags is explored again. The search stops after a bound on states per build
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::analysis::entry_points;
+use crate::navigation::Outcome;
+use crate::player::{success_chance, Player};
+use crate::Dialogues;
+
+pub const DEFAULT_STATE_LIMIT: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct State {
+    dialogue: String,
+    flags: BTreeSet<String>,
+    items: BTreeSet<String>,
+}
+
+impl State {
+    fn has(&self, condition: &str) -> bool {
+        self.flags.contains(condition) || self.items.contains(condition)
+    }
+}
+
+// Where a choice takes the player when it leaves the conversation
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum EndKind {
+    Leave,           // An option with no link ends the conversation
+    Link(String),    // "Conv/Id" into another conversation
+    MoveTo(String),  // A location, for options with move_to and no link
+    Missing(String), // A link to a dialogue this conversation doesn't have
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ending {
+    pub dialogue: String,
+    pub option: usize, // Index into the dialogue's options
+    pub success: bool, // Whether the option's challenge, if any, was passed
+    pub end: EndKind,
+    pub flags: Vec<String>,
+    pub items: Vec<String>,
+}
+
+// A dialogue the player can be left on with no option to pick
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Stuck {
+    pub dialogue: String,
+    pub flags: Vec<String>,
+    pub items: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ChallengeChance {
+    pub dialogue: String,
+    pub option: usize,
+    pub skill: String,
+    pub target: i32,
+    pub chance: f64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BuildReport {
+    pub build: String,
+    pub states: usize,
+    pub truncated: bool, // Hit the state limit, so there may be more of everything below
+    pub endings: Vec<Ending>,
+    pub stuck: Vec<Stuck>,
+    pub trapped: Vec<String>, // Dialogues in loops the player can't leave, sorted
+    pub challenges: Vec<ChallengeChance>,
+}
+
+// "tech 3, arts 3, bur 3, und 3"
+pub fn build_label(player: &Player) -> String {
+    format!("tech {}, arts {}, bur {}, und {}", player.tech, player.arts, player.bur, player.und)
+}
+
+// Every valid 12-point build, in a fixed order
+pub fn valid_builds() -> Vec<Player> {
+    let mut builds = vec![];
+    for tech in 1..=6 {
+        for arts in 1..=6 {
+            for bur in 1..=6 {
+                let player = Player::with_build(tech, arts, bur, 12 - tech - arts - bur);
+                if player.is_valid() {
+                    builds.push(player);
+                }
+            }
+        }
+    }
+    builds
+}
+
+fn sorted(set: &BTreeSet<String>) -> Vec<String> {
+    set.iter().cloned().collect()
+}
+
+// Explores the conversation from its entry points, starting with the build's flags and items
+pub fn explore(conversation: &str, dialogues: &Dialogues, build: &Player, limit: usize) -> BuildReport {
+    let start_flags: BTreeSet<String> = build.flags.iter().cloned().collect();
+    let start_items: BTreeSet<String> = build.items.iter().cloned().collect();
+
+    let mut seen: HashSet<State> = HashSet::new();
+    let mut queue = VecDeque::new();
+    for entry in entry_points(dialogues) {
+        let state = State {
+            dialogue: entry,
+            flags: start_flags.clone(),
+            items: start_items.clone(),
+        };
+        if seen.insert(state.clone()) {
+            queue.push_back(state);
+        }
+    }
+
+    let mut endings = BTreeSet::new();
+    let mut stuck = BTreeSet::new();
+    let mut challenges: HashMap<(String, usize), ChallengeChance> = HashMap::new();
+    let mut next_states: HashMap<State, Vec<State>> = HashMap::new();
+    let mut leaving: HashSet<State> = HashSet::new(); // States with a way out of the conversation
+    let mut stuck_states: HashSet<State> = HashSet::new();
+    let mut truncated = false;
+
+    while let Some(state) = queue.pop_front() {
+        let Some(dialogue) = dialogues.get(&state.dialogue) else {
+            continue;
+        };
+        let visible: Vec<(usize, _)> = dialogue
+            .options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| option.is_visible(|condition| state.has(condition)))
+            .collect();
+        if visible.is_empty() {
+            stuck.insert(Stuck {
+                dialogue: state.dialogue.clone(),
+                flags: sorted(&state.flags),
+                items: sorted(&state.items),
+            });
+            stuck_states.insert(state);
+            continue;
+        }
+
+        for (index, option) in visible {
+            let mut after = state.clone();
+            after.flags.extend(option.flags.iter().flatten().cloned());
+            after.items.extend(option.item_to_pickup.iter().cloned());
+
+            // Both outcomes, unless the build can't get one of them
+            let mut outcomes = vec![];
+            match build.challenge(option) {
+                Some((skill, value, target)) => {
+                    let chance = success_chance(value, target);
+                    challenges.entry((state.dialogue.clone(), index)).or_insert_with(|| ChallengeChance {
+                        dialogue: state.dialogue.clone(),
+                        option: index,
+                        skill: skill.to_lowercase(),
+                        target,
+                        chance,
+                    });
+                    if chance > 0.0 {
+                        outcomes.push(true);
+                    }
+                    if chance < 1.0 {
+                        outcomes.push(false);
+                    }
+                }
+                None => outcomes.push(true),
+            }
+
+            for success in outcomes {
+                let (next, end) = match option.outcome(success, conversation) {
+                    Outcome::Dialogue(id) if dialogues.contains_key(id) => (id, None),
+                    Outcome::Dialogue(id) => (id, Some(EndKind::Missing(id.to_string()))),
+                    Outcome::Link(link) => (link, Some(EndKind::Link(link.to_string()))),
+                    Outcome::Leave => ("", Some(EndKind::Leave)),
+                    Outcome::MoveTo(location) => (location, Some(EndKind::MoveTo(location.to_string()))),
+                };
+                if let Some(end) = end {
+                    leaving.insert(state.clone());
+                    endings.insert(Ending {
+                        dialogue: state.dialogue.clone(),
+                        option: index,
+                        success,
+                        end,
+                        flags: sorted(&after.flags),
+                        items: sorted(&after.items),
+                    });
+                    continue;
+                }
+
+                let next = State {
+                    dialogue: next.to_string(),
+                    ..after.clone()
+                };
+                next_states.entry(state.clone()).or_default().push(next.clone());
+                if seen.contains(&next) {
+                    continue;
+                }
+                if seen.len() >= limit {
+                    // Unexplored, so give it the benefit of the doubt rather than calling it trapped
+                    truncated = true;
+                    leaving.insert(state.clone());
+                    continue;
+                }
+                seen.insert(next.clone());
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Walk back from every state with a way out; whatever can't reach one, and isn't simply stuck, is trapped in a loop
+    let mut previous: HashMap<&State, Vec<&State>> = HashMap::new();
+    for (from, tos) in &next_states {
+        for to in tos {
+            previous.entry(to).or_default().push(from);
+        }
+    }
+    let mut can_finish: HashSet<&State> = HashSet::new();
+    let mut queue: VecDeque<&State> = leaving.iter().chain(&stuck_states).collect();
+    while let Some(state) = queue.pop_front() {
+        if !can_finish.insert(state) {
+            continue;
+        }
+        queue.extend(previous.get(state).into_iter().flatten().copied());
+    }
+    let trapped: BTreeSet<String> = seen
+        .iter()
+        .filter(|state| !can_finish.contains(state) && dialogues.contains_key(&state.dialogue))
+        .map(|state| state.dialogue.clone())
+        .collect();
+
+    let mut challenges: Vec<ChallengeChance> = challenges.into_values().collect();
+    challenges.sort_by(|a, b| (&a.dialogue, a.option).cmp(&(&b.dialogue, b.option)));
+
+    BuildReport {
+        build: build_label(build),
+        states: seen.len(),
+        truncated,
+        endings: endings.into_iter().collect(),
+        stuck: stuck.into_iter().collect(),
+        trapped: trapped.into_iter().collect(),
+        challenges,
+    }
+}
+
+fn held(flags: &[String], items: &[String]) -> String {
+    let held: Vec<&str> = flags.iter().chain(items).map(String::as_str).collect();
+    if held.is_empty() {
+        String::new()
+    } else {
+        format!(" holding {}", held.join(", "))
+    }
+}
+
+impl fmt::Display for EndKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EndKind::Leave => write!(f, "leaves the conversation"),
+            EndKind::Link(link) => write!(f, "goes to {}", link),
+            EndKind::MoveTo(location) => write!(f, "walks to {}", location),
+            EndKind::Missing(link) => write!(f, "links to missing dialogue {}", link),
+        }
+    }
+}
+
+impl fmt::Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} state(s){}", self.build, self.states, if self.truncated { ", stopped at the limit" } else { "" })?;
+        writeln!(f, "  Endings: {}", self.endings.len())?;
+        for ending in &self.endings {
+            let outcome = if ending.success { "" } else { " (failed)" };
+            writeln!(
+                f,
+                "    {} option {}{} {}{}",
+                ending.dialogue,
+                ending.option + 1,
+                outcome,
+                ending.end,
+                held(&ending.flags, &ending.items)
+            )?;
+        }
+        for stuck in &self.stuck {
+            writeln!(f, "  Stuck at {}{}", stuck.dialogue, held(&stuck.flags, &stuck.items))?;
+        }
+        if !self.trapped.is_empty() {
+            writeln!(f, "  Trapped in a loop: {}", self.trapped.join(", "))?;
+        }
+        for challenge in &self.challenges {
+            writeln!(
+                f,
+                "  {} option {}: {} {} passes {:.0}% of the time",
+                challenge.dialogue,
+                challenge.option + 1,
+                challenge.skill,
+                challenge.target,
+                challenge.chance * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dialogue, DialogueOption};
+
+    fn link(to: &str) -> DialogueOption {
+        DialogueOption {
+            success_dialogue: Some(to.to_string()),
+            ..Default::default()
+        }
+    }
+
+    // The default option links to Start, so options that end the conversation say so
+    fn leave() -> DialogueOption {
+        DialogueOption {
+            success_dialogue: None,
+            ..Default::default()
+        }
+    }
+
+    fn conversation(dialogues: Vec<(&str, Vec<DialogueOption>)>) -> Dialogues {
+        dialogues
+            .into_iter()
+            .map(|(id, options)| (id.to_string(), Dialogue { options, ..Default::default() }))
+            .collect()
+    }
+
+    fn build(flags: &[&str]) -> Player {
+        let mut player = Player::with_build(3, 3, 3, 3);
+        player.flags = flags.iter().map(|flag| flag.to_string()).collect();
+        player
+    }
+
+    #[test]
+    fn hidden_options_leave_the_player_stuck() {
+        let dialogues = conversation(vec![(
+            "Start",
+            vec![DialogueOption {
+                visible_when: Some("key".to_string()),
+                ..leave()
+            }],
+        )]);
+        let report = explore("Clock", &dialogues, &build(&[]), DEFAULT_STATE_LIMIT);
+        assert_eq!(report.stuck.len(), 1);
+        assert!(report.endings.is_empty());
+
+        let report = explore("Clock", &dialogues, &build(&["key"]), DEFAULT_STATE_LIMIT);
+        assert!(report.stuck.is_empty());
+        assert_eq!(report.endings[0].end, EndKind::Leave);
+    }
+
+    #[test]
+    fn a_loop_is_only_a_trap_without_the_flag_that_opens_it() {
+        let climb = DialogueOption {
+            visible_when: Some("rope".to_string()),
+            ..leave()
+        };
+        let search = DialogueOption {
+            flags: Some(vec!["rope".to_string()]),
+            ..link("Pit")
+        };
+        let trapped = conversation(vec![("Start", vec![link("Pit")]), ("Pit", vec![link("Pit"), climb.clone()])]);
+        let report = explore("Clock", &trapped, &build(&[]), DEFAULT_STATE_LIMIT);
+        assert_eq!(report.trapped, vec!["Pit", "Start"]);
+        assert!(report.endings.is_empty());
+
+        let escapable = conversation(vec![("Start", vec![link("Pit")]), ("Pit", vec![search, climb])]);
+        let report = explore("Clock", &escapable, &build(&[]), DEFAULT_STATE_LIMIT);
+        assert!(report.trapped.is_empty());
+        assert_eq!(report.endings.len(), 1);
+        assert_eq!(report.endings[0].flags, vec!["rope"]);
+    }
+
+    #[test]
+    fn challenges_follow_both_outcomes() {
+        // Only double six passes, and double one always fails, so both ways are open to every build
+        let gamble = DialogueOption {
+            challenge_attribute: Some("Delusion".to_string()),
+            challenge_number: Some(100),
+            success_dialogue: Some("Clock/Won".to_string()),
+            failure_dialogue: Some("Intro/Start".to_string()),
+            ..Default::default()
+        };
+        let walk = DialogueOption {
+            move_to: Some("Street".to_string()),
+            ..leave()
+        };
+        let dialogues = conversation(vec![("Start", vec![gamble]), ("Won", vec![walk])]);
+        let report = explore("Clock", &dialogues, &build(&[]), DEFAULT_STATE_LIMIT);
+
+        let ends: Vec<(&str, bool, &EndKind)> =
+            report.endings.iter().map(|ending| (ending.dialogue.as_str(), ending.success, &ending.end)).collect();
+        assert_eq!(
+            ends,
+            vec![
+                ("Start", false, &EndKind::Link("Intro/Start".to_string())),
+                ("Won", true, &EndKind::MoveTo("Street".to_string())),
+            ]
+        );
+        assert_eq!(report.challenges.len(), 1);
+        assert!((report.challenges[0].chance - 1.0 / 36.0).abs() < 1e-9);
+    }
+}
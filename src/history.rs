@@ -0,0 +1,346 @@
+// Command-based edit history for the dialogue editor
+// Every change to a conversation is recorded as a command that knows how to undo and redo itself
+
+use crate::{delete_dialogue, move_entry, reorder_dialogues, restore_dialogue, DeletedDialogue, Dialogue, DialogueReference, Dialogues};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditCommand {
+    // Any change inside a single dialogue: field edits, option and passive check add/remove
+    EditDialogue {
+        id: String,
+        before: Dialogue,
+        after: Dialogue,
+    },
+    CreateDialogue {
+        id: String,
+        dialogue: Dialogue,
+    },
+    DeleteDialogue {
+        deleted: DeletedDialogue,
+        redirect_to: Option<String>,
+    },
+    RenameDialogue {
+        old_id: String,
+        new_id: String,
+        references: Vec<DialogueReference>, // The links that pointed at old_id before the rename
+    },
+    ReorderDialogues {
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+    // Links pointed from one target to another, e.g. "Clock/Old" to "Clock/New" when another file renames a dialogue,
+    // or cleared when it deletes one
+    RewriteLinks {
+        from: String,
+        to: Option<String>,
+        references: Vec<DialogueReference>, // The links that pointed at from
+    },
+    // Several commands that one action applied together, undone and redone as one
+    Group {
+        label: String,
+        commands: Vec<EditCommand>,
+    },
+}
+
+impl EditCommand {
+    pub fn label(&self) -> String {
+        match self {
+            EditCommand::EditDialogue { id, before, after } => {
+                if after.options.len() > before.options.len() {
+                    format!("Add option to {}", id)
+                } else if after.options.len() < before.options.len() {
+                    format!("Remove option from {}", id)
+                } else if after.passive_check.len() > before.passive_check.len() {
+                    format!("Add passive check to {}", id)
+                } else if after.passive_check.len() < before.passive_check.len() {
+                    format!("Remove passive check from {}", id)
+                } else {
+                    format!("Edit {}", id)
+                }
+            }
+            EditCommand::CreateDialogue { id, .. } => format!("Create {}", id),
+            EditCommand::DeleteDialogue { deleted, .. } => format!("Delete {}", deleted.id),
+            EditCommand::RenameDialogue { old_id, new_id, .. } => format!("Rename {} to {}", old_id, new_id),
+            EditCommand::ReorderDialogues { .. } => "Reorder dialogues".to_string(),
+            EditCommand::RewriteLinks { from, to: Some(to), .. } => format!("Point links to {} at {}", from, to),
+            EditCommand::RewriteLinks { from, to: None, .. } => format!("Clear links to {}", from),
+            EditCommand::Group { label, .. } => label.clone(),
+        }
+    }
+
+    // The dialogue the editor should show once this command has been undone or redone
+    pub fn selected_after(&self, undone: bool) -> Option<&str> {
+        match self {
+            EditCommand::EditDialogue { id, .. } => Some(id),
+            EditCommand::CreateDialogue { id, .. } => (!undone).then_some(id.as_str()),
+            EditCommand::DeleteDialogue { deleted, .. } => undone.then_some(deleted.id.as_str()),
+            EditCommand::RenameDialogue { old_id, new_id, .. } => Some(if undone { old_id } else { new_id }),
+            EditCommand::ReorderDialogues { .. } | EditCommand::RewriteLinks { .. } => None,
+            EditCommand::Group { commands, .. } => commands.first()?.selected_after(undone),
+        }
+    }
+
+    // Carries out a command that hasn't been applied yet, e.g. one step of a group being built
+    pub fn apply(&self, dialogues: &mut Dialogues) {
+        self.redo(dialogues);
+    }
+
+    fn undo(&self, dialogues: &mut Dialogues) {
+        match self {
+            EditCommand::EditDialogue { id, before, .. } => {
+                dialogues.insert(id.clone(), before.clone());
+            }
+            EditCommand::CreateDialogue { id, .. } => {
+                dialogues.shift_remove(id);
+            }
+            EditCommand::DeleteDialogue { deleted, .. } => {
+                restore_dialogue(dialogues, deleted.clone());
+            }
+            EditCommand::RenameDialogue { old_id, new_id, references } => {
+                move_entry(dialogues, new_id, old_id);
+                set_targets(dialogues, references, Some(old_id));
+            }
+            EditCommand::ReorderDialogues { before, .. } => reorder_dialogues(dialogues, before),
+            EditCommand::RewriteLinks { from, references, .. } => set_targets(dialogues, references, Some(from)),
+            EditCommand::Group { commands, .. } => {
+                for command in commands.iter().rev() {
+                    command.undo(dialogues);
+                }
+            }
+        }
+    }
+
+    fn redo(&self, dialogues: &mut Dialogues) {
+        match self {
+            EditCommand::EditDialogue { id, after, .. } => {
+                dialogues.insert(id.clone(), after.clone());
+            }
+            EditCommand::CreateDialogue { id, dialogue } => {
+                dialogues.insert(id.clone(), dialogue.clone());
+            }
+            EditCommand::DeleteDialogue { deleted, redirect_to } => {
+                delete_dialogue(dialogues, &deleted.id, redirect_to.as_deref());
+            }
+            EditCommand::RenameDialogue { old_id, new_id, references } => {
+                // The references name dialogues as they were before the rename, a self-link included
+                set_targets(dialogues, references, Some(new_id));
+                move_entry(dialogues, old_id, new_id);
+            }
+            EditCommand::ReorderDialogues { after, .. } => reorder_dialogues(dialogues, after),
+            EditCommand::RewriteLinks { to, references, .. } => set_targets(dialogues, references, to.as_deref()),
+            EditCommand::Group { commands, .. } => {
+                for command in commands {
+                    command.redo(dialogues);
+                }
+            }
+        }
+    }
+}
+
+fn set_targets(dialogues: &mut Dialogues, references: &[DialogueReference], target: Option<&str>) {
+    for reference in references {
+        if let Some(link) = reference.target_mut(dialogues) {
+            *link = target.map(str::to_string);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    done: Vec<EditCommand>,   // Oldest first, the last entry is the next to undo
+    undone: Vec<EditCommand>, // The last entry is the next to redo
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Records a command that has already been applied; a fresh edit discards the redo branch
+    pub fn record(&mut self, command: EditCommand) {
+        self.done.push(command);
+        self.undone.clear();
+    }
+
+    // Carries out a command that hasn't been applied yet and records it
+    pub fn apply(&mut self, command: EditCommand, dialogues: &mut Dialogues) {
+        command.apply(dialogues);
+        self.record(command);
+    }
+
+    pub fn undo(&mut self, dialogues: &mut Dialogues) -> Option<&EditCommand> {
+        let command = self.done.pop()?;
+        command.undo(dialogues);
+        self.undone.push(command);
+        self.undone.last()
+    }
+
+    pub fn redo(&mut self, dialogues: &mut Dialogues) -> Option<&EditCommand> {
+        let command = self.undone.pop()?;
+        command.redo(dialogues);
+        self.done.push(command);
+        self.done.last()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub fn done(&self) -> &[EditCommand] {
+        &self.done
+    }
+
+    // Redo entries in the order they would be redone
+    pub fn undone(&self) -> impl Iterator<Item = &EditCommand> {
+        self.undone.iter().rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{find_references, rename_dialogue, DialogueOption};
+
+    fn dialogue(intro: &str, links: &[&str]) -> Dialogue {
+        let options = links
+            .iter()
+            .map(|link| DialogueOption {
+                success_dialogue: Some(link.to_string()),
+                ..Default::default()
+            })
+            .collect();
+        Dialogue {
+            intro: intro.to_string(),
+            options,
+            ..Default::default()
+        }
+    }
+
+    fn file() -> Dialogues {
+        [("Start", dialogue("Hi", &["Loop"])), ("Loop", dialogue("Again?", &["Loop", "Start"]))]
+            .into_iter()
+            .map(|(id, dialogue)| (id.to_string(), dialogue))
+            .collect()
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_history() {
+        let mut dialogues = file();
+        let mut history = EditHistory::new();
+
+        let before = dialogues["Start"].clone();
+        dialogues["Start"].intro = "Hello".to_string();
+        history.record(EditCommand::EditDialogue {
+            id: "Start".to_string(),
+            before,
+            after: dialogues["Start"].clone(),
+        });
+        let deleted = delete_dialogue(&mut dialogues, "Loop", Some("Start")).unwrap();
+        history.record(EditCommand::DeleteDialogue {
+            deleted,
+            redirect_to: Some("Start".to_string()),
+        });
+        let edited = dialogues.clone();
+
+        assert_eq!(history.undo(&mut dialogues).unwrap().label(), "Delete Loop");
+        assert_eq!(history.undo(&mut dialogues).unwrap().label(), "Edit Start");
+        assert!(history.undo(&mut dialogues).is_none());
+        assert_eq!(dialogues, file());
+
+        history.redo(&mut dialogues);
+        history.redo(&mut dialogues);
+        assert!(!history.can_redo());
+        assert_eq!(dialogues, edited);
+    }
+
+    #[test]
+    fn recording_drops_the_redo_branch() {
+        let mut dialogues = file();
+        let mut history = EditHistory::new();
+        history.record(EditCommand::CreateDialogue {
+            id: "Extra".to_string(),
+            dialogue: Dialogue::default(),
+        });
+        history.undo(&mut dialogues);
+        assert!(history.can_redo());
+        history.record(EditCommand::ReorderDialogues {
+            before: vec!["Start".to_string(), "Loop".to_string()],
+            after: vec!["Loop".to_string(), "Start".to_string()],
+        });
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn rename_with_a_self_link_redoes() {
+        let mut dialogues = file();
+        let mut history = EditHistory::new();
+        let references = find_references(&dialogues, "Loop");
+        rename_dialogue(&mut dialogues, "Loop", "Again").unwrap();
+        history.record(EditCommand::RenameDialogue {
+            old_id: "Loop".to_string(),
+            new_id: "Again".to_string(),
+            references,
+        });
+        let renamed = dialogues.clone();
+
+        history.undo(&mut dialogues);
+        assert_eq!(dialogues, file());
+        history.redo(&mut dialogues);
+        assert_eq!(dialogues, renamed);
+        assert_eq!(dialogues["Again"].options[0].success_dialogue.as_deref(), Some("Again"));
+    }
+
+    #[test]
+    fn cleared_links_come_back_on_undo() {
+        let mut dialogues = file();
+        dialogues["Start"].options[0].success_dialogue = Some("Clock/Gone".to_string());
+        let linked = dialogues.clone();
+        let mut history = EditHistory::new();
+        history.apply(
+            EditCommand::RewriteLinks {
+                from: "Clock/Gone".to_string(),
+                to: None,
+                references: find_references(&dialogues, "Clock/Gone"),
+            },
+            &mut dialogues,
+        );
+        assert_eq!(dialogues["Start"].options[0].success_dialogue, None);
+        assert_eq!(history.undo(&mut dialogues).unwrap().label(), "Clear links to Clock/Gone");
+        assert_eq!(dialogues, linked);
+    }
+
+    #[test]
+    fn groups_undo_as_one_step() {
+        let mut dialogues = file();
+        let mut history = EditHistory::new();
+        let before = dialogues["Start"].clone();
+        dialogues["Start"].options[0].success_dialogue = Some("New".to_string());
+        dialogues.insert("New".to_string(), Dialogue::default());
+        history.record(EditCommand::Group {
+            label: "Create and link New".to_string(),
+            commands: vec![
+                EditCommand::EditDialogue {
+                    id: "Start".to_string(),
+                    before,
+                    after: dialogues["Start"].clone(),
+                },
+                EditCommand::CreateDialogue {
+                    id: "New".to_string(),
+                    dialogue: Dialogue::default(),
+                },
+            ],
+        });
+        let linked = dialogues.clone();
+
+        let undone = history.undo(&mut dialogues).unwrap();
+        assert_eq!(undone.selected_after(true), Some("Start"));
+        assert_eq!(dialogues, file());
+        assert!(!history.can_undo());
+        history.redo(&mut dialogues);
+        assert_eq!(dialogues, linked);
+    }
+}
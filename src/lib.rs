@@ -3,63 +3,147 @@
 // I'm trying to edit this in neovim
 
 use std::io;
+use std::fmt;
 use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::path::Path;
 use serde::{Deserialize, Serialize};
+use loading::{parse_dialogues, Loaded, Strictness};
 use std::fs;
 //use dialoguer::{Input, Select, Confirm};
 
-
-pub fn load_dialogues(file_path: &str) -> HashMap<String, Dialogue> {
+pub mod analysis;
+pub mod exploration;
+pub mod history;
+pub mod loading;
+pub mod localization;
+pub mod markup;
+pub mod navigation;
+pub mod player;
+pub mod playtest;
+pub mod project;
+pub mod runtime;
+pub mod scene;
+pub mod schema;
+pub mod speakers;
+pub mod stats;
+pub mod transcript;
+pub mod validation;
+
+// Dialogues by ID, kept in the order they were written so files read top to bottom like the scene plays
+pub type Dialogues = IndexMap<String, Dialogue>;
+
+
+pub fn load_dialogues(file_path: &str) -> Dialogues {
     // Read the JSON file into a string
     let file_content = fs::read_to_string(file_path)
         .expect("Failed to read the dialogue JSON file");
     
-    // Parse the JSON string into a HashMap
-    let dialogues_result: Result<HashMap<String, Dialogue>, _> = serde_json::from_str(&file_content);
-
-    match dialogues_result {
-        Ok(mut dialogues) => {
+    // Parse it a dialogue at a time, so one broken dialogue doesn't take the others with it
+    match parse_dialogues(&file_content, Strictness::Lenient) {
+        Ok(loaded) => {
+            report_load_issues(file_path, &loaded);
+            let mut dialogues = loaded.dialogues;
             // Validate and apply defaults to each Dialogue
             for (_, dialogue) in dialogues.iter_mut() {
                 validate_and_fill_defaults(dialogue);
             }
             dialogues
         },
-        Err(_) => {
-            eprintln!("Error parsing dialogues. Using default dialogue.");
-            let mut dialogues = HashMap::new();
+        Err(err) => {
+            eprintln!("Error parsing dialogues in {}: {}. Using default dialogue.", file_path, err);
+            let mut dialogues = Dialogues::new();
             dialogues.insert("Default".to_string(), Dialogue::default());
             dialogues
         }
     }
 }
 
-fn validate_and_fill_defaults(dialogue: &mut Dialogue) {
-    // Ensure `speaker` and `intro` are not empty
-    if dialogue.speaker.is_empty() {
-        dialogue.speaker = Dialogue::default().speaker;
+fn report_load_issues(file_path: &str, loaded: &Loaded) {
+    for issue in &loaded.issues {
+        eprintln!("{}: {}", file_path, issue);
+    }
+}
+
+// Older files store "" where a field was meant to be unset; treat those as None
+// Returns how many fields were cleared, so the migration can report what it changed
+pub fn normalize_dialogue(dialogue: &mut Dialogue) -> usize {
+    fn clear_empty(field: &mut Option<String>) -> usize {
+        if field.as_deref() == Some("") {
+            *field = None;
+            1
+        } else {
+            0
+        }
+    }
+
+    let mut cleared = clear_empty(&mut dialogue.background) + clear_empty(&mut dialogue.portrait);
+    for option in dialogue.options.iter_mut() {
+        cleared += clear_empty(&mut option.challenge_attribute);
+        cleared += clear_empty(&mut option.success_dialogue);
+        cleared += clear_empty(&mut option.failure_dialogue);
+        cleared += clear_empty(&mut option.item_to_pickup);
+        cleared += clear_empty(&mut option.visible_when);
+        cleared += clear_empty(&mut option.move_to);
+        if option.flags.as_ref().is_some_and(|flags| flags.is_empty()) {
+            option.flags = None;
+            cleared += 1;
+        }
+    }
+    for check in dialogue.passive_check.iter_mut() {
+        cleared += clear_empty(&mut check.success_text);
+        cleared += clear_empty(&mut check.failure_text);
+        cleared += clear_empty(&mut check.speaker);
+    }
+    cleared
+}
+
+pub fn normalize_dialogues(dialogues: &mut Dialogues) -> usize {
+    dialogues.values_mut().map(normalize_dialogue).sum()
+}
+
+// Cleans a dialogue file in place without applying any of the loader's defaults
+// Returns how many empty strings were turned into nulls
+pub fn migrate_file(file_path: &str) -> Result<usize, io::Error> {
+    let content = fs::read_to_string(file_path)?;
+    let mut dialogues: Dialogues = serde_json::from_str(&content)?;
+    let cleared = normalize_dialogues(&mut dialogues);
+    // Unknown fields would be lost on saving, so leave the file alone until they're fixed
+    if let Some(issue) = parse_dialogues(&content, Strictness::Strict)?.issues.first() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, issue.to_string()));
+    }
+    if cleared > 0 {
+        write_dialogues(&dialogues, file_path)?;
     }
+    Ok(cleared)
+}
+
+fn validate_and_fill_defaults(dialogue: &mut Dialogue) {
+    // Ensure `intro` is not empty; an empty speaker is narration
     if dialogue.intro.is_empty() {
         dialogue.intro = Dialogue::default().intro;
     }
 
     // Apply defaults for `options`
+    // Links stay as written: normalize_dialogue has already turned "" into None, and an option
+    // without a link ends the conversation rather than starting it over
+    let leave = DialogueOption {
+        success_dialogue: None,
+        ..Default::default()
+    };
     if dialogue.options.is_empty() {
-        dialogue.options = vec![DialogueOption::default()];
+        dialogue.options = vec![leave];
     } else {
         for option in dialogue.options.iter_mut() {
             if option.description.is_empty() {
-                option.description = DialogueOption::default().description;
-            }
-            if option.success_dialogue.is_none() {
-                option.success_dialogue = DialogueOption::default().success_dialogue;
+                option.description = leave.description.clone();
             }
         }
     }
 
-    // Validate `PassiveCheck` (all fields must be present)
+    // A check needs a skill and a target; without a speaker it's narration
     dialogue.passive_check.retain(|check| {
-        if check.skill.is_empty() || check.target <= 0 || check.speaker.is_none() {
+        if check.skill.is_empty() || check.target <= 0 {
             eprintln!("Invalid PassiveCheck found and removed: {:?}", check);
             false // Remove invalid checks
         } else {
@@ -68,25 +152,432 @@ fn validate_and_fill_defaults(dialogue: &mut Dialogue) {
     });
 }
 
-pub fn create_dialogue(dialogues: &mut HashMap<String, Dialogue>) -> String {
-    let id = format!("Dialogue_{}", dialogues.len() + 1);
-    dialogues.insert(
-        id.clone(),
-        Dialogue {
-            speaker: "New Speaker".to_string(),
-            intro: "New Intro Text".to_string(),
-            options: vec![],
-            passive_check: vec![],
-            xp_reward: None,
-            is_hidden: false,
-            time: None,
-        },
-    );
+pub fn create_dialogue(dialogues: &mut Dialogues) -> String {
+    // Skip numbers left in use after deletes and renames, so an existing dialogue is never overwritten
+    let mut n = dialogues.len() + 1;
+    while dialogues.contains_key(&format!("Dialogue_{}", n)) {
+        n += 1;
+    }
+    let id = format!("Dialogue_{}", n);
+    dialogues.insert(id.clone(), placeholder_dialogue());
     id // Return the new dialogue ID
 }
 
+// Creates a placeholder dialogue under a chosen ID, e.g. when an option links to a dialogue not yet written
+pub fn create_dialogue_with_id(dialogues: &mut Dialogues, id: &str) -> Result<(), RenameError> {
+    if id.is_empty() {
+        return Err(RenameError::EmptyId);
+    }
+    if dialogues.contains_key(id) {
+        return Err(RenameError::Collision(id.to_string()));
+    }
+    dialogues.insert(id.to_string(), placeholder_dialogue());
+    Ok(())
+}
+
+// What a new dialogue says until someone writes it
+pub const PLACEHOLDER_SPEAKER: &str = "New Speaker";
+pub const PLACEHOLDER_INTRO: &str = "New Intro Text";
+
+fn placeholder_dialogue() -> Dialogue {
+    Dialogue {
+        speaker: PLACEHOLDER_SPEAKER.to_string(),
+        intro: PLACEHOLDER_INTRO.to_string(),
+        options: vec![],
+        passive_check: vec![],
+        xp_reward: None,
+        is_hidden: false,
+        time: None,
+        background: None,
+        portrait: None,
+    }
+}
+
+// The skills a challenge or passive check can test, spelled as they appear in dialogue files
+pub const SKILLS: [&str; 16] = [
+    "checkmate",
+    "rocketry",
+    "pathology",
+    "civic engineering",
+    "apparatchik",
+    "quota",
+    "robot",
+    "dossier",
+    "delusion",
+    "lens",
+    "opera",
+    "transcendence",
+    "gunsmoke",
+    "prohibition",
+    "gizmo",
+    "oldtime religion",
+];
+
+// The item catalogue is a JSON list of item names; a missing file just means no items yet
+pub fn load_items(file_path: &str) -> Vec<String> {
+    match fs::read_to_string(file_path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|_| {
+            eprintln!("Error parsing item catalogue {}. Using no items.", file_path);
+            vec![]
+        }),
+        Err(_) => vec![],
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenameError {
+    EmptyId,
+    NotFound(String),
+    Collision(String),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::EmptyId => write!(f, "Dialogue ID cannot be empty"),
+            RenameError::NotFound(id) => write!(f, "No dialogue with ID '{}'", id),
+            RenameError::Collision(id) => write!(f, "A dialogue with ID '{}' already exists", id),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
+
+// Points every success_dialogue/failure_dialogue that targets old_id at new_id instead
+// Returns how many references were rewritten, so other files in a project can be patched too
+pub fn rewrite_references(dialogues: &mut Dialogues, old_id: &str, new_id: &str) -> usize {
+    let mut rewritten = 0;
+    for dialogue in dialogues.values_mut() {
+        for option in dialogue.options.iter_mut() {
+            for target in [&mut option.success_dialogue, &mut option.failure_dialogue] {
+                if target.as_deref() == Some(old_id) {
+                    *target = Some(new_id.to_string());
+                    rewritten += 1;
+                }
+            }
+        }
+    }
+    rewritten
+}
+
+// Moves a dialogue to a new ID and rewrites every reference to it in the same map
+// Nothing is changed unless the rename can go through, so a collision never loses a dialogue
+pub fn rename_dialogue(dialogues: &mut Dialogues, old_id: &str, new_id: &str) -> Result<usize, RenameError> {
+    if new_id.is_empty() {
+        return Err(RenameError::EmptyId);
+    }
+    if !dialogues.contains_key(old_id) {
+        return Err(RenameError::NotFound(old_id.to_string()));
+    }
+    if old_id == new_id {
+        return Ok(0);
+    }
+    if dialogues.contains_key(new_id) {
+        return Err(RenameError::Collision(new_id.to_string()));
+    }
+
+    move_entry(dialogues, old_id, new_id);
+    Ok(rewrite_references(dialogues, old_id, new_id))
+}
+
+// Changes a dialogue's key without moving it in the file order
+fn move_entry(dialogues: &mut Dialogues, old_id: &str, new_id: &str) -> bool {
+    let Some((index, _, dialogue)) = dialogues.shift_remove_full(old_id) else {
+        return false;
+    };
+    dialogues.shift_insert(index, new_id.to_string(), dialogue);
+    true
+}
+
+// Puts the dialogues in the given order; IDs missing from it keep their relative order at the end
+pub fn reorder_dialogues(dialogues: &mut Dialogues, order: &[String]) {
+    let position: HashMap<&str, usize> = order.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+    dialogues.sort_by_cached_key(|id, _| position.get(id.as_str()).copied().unwrap_or(usize::MAX));
+}
+
+// Renames a dialogue of a conversation and patches the links the rest of the project holds to it
+// Bare IDs only mean something inside their own file, so the other files only have "Conversation/Old" rewritten
+pub fn rename_dialogue_in_project(
+    dialogues: &mut Dialogues,
+    conversation: &str,
+    other_files: &mut [&mut Dialogues],
+    old_id: &str,
+    new_id: &str,
+) -> Result<usize, RenameError> {
+    let mut rewritten = rename_dialogue(dialogues, old_id, new_id)?;
+    let (old_link, new_link) = (format!("{}/{}", conversation, old_id), format!("{}/{}", conversation, new_id));
+    rewritten += rewrite_references(dialogues, &old_link, &new_link);
+    for other in other_files.iter_mut() {
+        rewritten += rewrite_references(other, &old_link, &new_link);
+    }
+    Ok(rewritten)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Success,
+    Failure,
+}
 
-pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogue, temp_id: &mut String) -> Option<String> {
+// One option that links to a dialogue through its success or failure branch
+#[derive(Debug, Clone, PartialEq)]
+pub struct DialogueReference {
+    pub dialogue_id: String,
+    pub option_index: usize,
+    pub kind: ReferenceKind,
+}
+
+impl DialogueReference {
+    fn target_mut<'a>(&self, dialogues: &'a mut Dialogues) -> Option<&'a mut Option<String>> {
+        let option = dialogues.get_mut(&self.dialogue_id)?.options.get_mut(self.option_index)?;
+        Some(match self.kind {
+            ReferenceKind::Success => &mut option.success_dialogue,
+            ReferenceKind::Failure => &mut option.failure_dialogue,
+        })
+    }
+}
+
+// Lists every option that points at target_id, in file order
+pub fn find_references(dialogues: &Dialogues, target_id: &str) -> Vec<DialogueReference> {
+    let mut references = vec![];
+    for (id, dialogue) in dialogues {
+        for (option_index, option) in dialogue.options.iter().enumerate() {
+            if option.success_dialogue.as_deref() == Some(target_id) {
+                references.push(DialogueReference { dialogue_id: id.clone(), option_index, kind: ReferenceKind::Success });
+            }
+            if option.failure_dialogue.as_deref() == Some(target_id) {
+                references.push(DialogueReference { dialogue_id: id.clone(), option_index, kind: ReferenceKind::Failure });
+            }
+        }
+    }
+    references
+}
+
+// Everything needed to put a deleted dialogue back exactly as it was
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletedDialogue {
+    pub id: String,
+    pub index: usize, // Position in the file, so undo puts it back where it was
+    pub dialogue: Dialogue,
+    pub references: Vec<DialogueReference>,
+}
+
+// Removes a dialogue and points its incoming references at redirect_to, or clears them when None
+pub fn delete_dialogue(dialogues: &mut Dialogues, id: &str, redirect_to: Option<&str>) -> Option<DeletedDialogue> {
+    let references = find_references(dialogues, id);
+    let (index, _, dialogue) = dialogues.shift_remove_full(id)?;
+
+    for reference in &references {
+        if let Some(target) = reference.target_mut(dialogues) {
+            *target = redirect_to.map(str::to_string);
+        }
+    }
+
+    Some(DeletedDialogue {
+        id: id.to_string(),
+        index,
+        dialogue,
+        references,
+    })
+}
+
+// Undoes delete_dialogue, re-pointing the references that were redirected or cleared
+pub fn restore_dialogue(dialogues: &mut Dialogues, deleted: DeletedDialogue) {
+    for reference in &deleted.references {
+        if let Some(target) = reference.target_mut(dialogues) {
+            *target = Some(deleted.id.clone());
+        }
+    }
+    let index = deleted.index.min(dialogues.len());
+    dialogues.shift_insert(index, deleted.id, deleted.dialogue);
+}
+
+// Per-item edits for the lists inside a dialogue (options, passive checks)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListAction {
+    Remove(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Duplicate(usize),
+    Move { from: usize, to: usize },
+}
+
+// Out-of-range indices are ignored, so a stale action from a previous frame can't panic
+pub fn apply_list_action<T: Clone>(items: &mut Vec<T>, action: ListAction) {
+    let len = items.len();
+    match action {
+        ListAction::Remove(i) if i < len => {
+            items.remove(i);
+        }
+        ListAction::MoveUp(i) if i > 0 && i < len => items.swap(i - 1, i),
+        ListAction::MoveDown(i) if i + 1 < len => items.swap(i, i + 1),
+        ListAction::Duplicate(i) if i < len => {
+            let copy = items[i].clone();
+            items.insert(i + 1, copy);
+        }
+        ListAction::Move { from, to } if from < len && to < len && from != to => {
+            let item = items.remove(from);
+            items.insert(to, item);
+        }
+        _ => {}
+    }
+}
+
+// Drag payload, tagged with the list it came from so options can't be dropped among passive checks
+struct ListDrag {
+    list: &'static str,
+    index: usize,
+}
+
+fn list_item_controls(ui: &mut egui::Ui, list: &'static str, index: usize, len: usize) -> Option<ListAction> {
+    let mut action = None;
+    ui.horizontal(|ui| {
+        ui.dnd_drag_source(egui::Id::new((list, "drag", index)), ListDrag { list, index }, |ui| {
+            ui.label("☰");
+        });
+        ui.label(format!("#{}", index + 1));
+
+        if ui.add_enabled(index > 0, egui::Button::new("⏶")).on_hover_text("Move up").clicked() {
+            action = Some(ListAction::MoveUp(index));
+        }
+        if ui.add_enabled(index + 1 < len, egui::Button::new("⏷")).on_hover_text("Move down").clicked() {
+            action = Some(ListAction::MoveDown(index));
+        }
+        if ui.button("Duplicate").clicked() {
+            action = Some(ListAction::Duplicate(index));
+        }
+        if ui.button("Delete").clicked() {
+            action = Some(ListAction::Remove(index));
+        }
+    });
+    action
+}
+
+fn list_drop_target(response: &egui::Response, list: &'static str, index: usize) -> Option<ListAction> {
+    let drag = response.dnd_release_payload::<ListDrag>()?;
+    (drag.list == list).then_some(ListAction::Move { from: drag.index, to: index })
+}
+
+// What the editor's pickers offer, so links and checks can only name things that exist
+pub struct EditorCatalogue {
+    pub dialogue_ids: Vec<String>,
+    pub skills: Vec<String>,
+    pub items: Vec<String>,
+    pub locations: Vec<String>,
+    pub speakers: Vec<String>, // Speaker IDs from the catalogue
+}
+
+impl EditorCatalogue {
+    pub fn new(dialogues: &Dialogues, items: &[String]) -> Self {
+        let mut dialogue_ids: Vec<String> = dialogues.keys().cloned().collect();
+        dialogue_ids.sort();
+        Self {
+            dialogue_ids,
+            skills: SKILLS.iter().map(|skill| skill.to_string()).collect(),
+            items: items.to_vec(),
+            locations: vec![],
+            speakers: vec![],
+        }
+    }
+
+    // Offers another conversation's dialogues as "Conversation/ID" link targets
+    pub fn add_conversation(&mut self, conversation: &str, dialogues: &Dialogues) {
+        self.dialogue_ids
+            .extend(dialogues.keys().map(|id| format!("{}/{}", conversation, id)));
+    }
+}
+
+// Changes that reach beyond the dialogue being edited, carried out by the caller
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogueEditAction {
+    Rename(String),
+    CreateDialogue(String),
+}
+
+// Text field for an Option<String> that shows "unset" instead of quietly turning None into ""
+fn optional_text(ui: &mut egui::Ui, value: &mut Option<String>, multiline: bool) {
+    match value {
+        Some(text) => {
+            if multiline {
+                ui.text_edit_multiline(text);
+            } else {
+                ui.text_edit_singleline(text);
+            }
+            if ui.small_button("Unset").clicked() {
+                *value = None;
+            }
+        }
+        None => {
+            ui.weak("(unset)");
+            if ui.small_button("Set").clicked() {
+                *value = Some(String::new());
+            }
+        }
+    }
+}
+
+// Combo box over a fixed set of choices; a value outside the set is shown in red
+fn choice_picker(ui: &mut egui::Ui, id_salt: impl std::hash::Hash, value: &mut Option<String>, choices: &[String]) {
+    let current = value.as_deref().filter(|v| !v.is_empty());
+    let valid = current.is_none_or(|v| choices.iter().any(|choice| choice == v));
+    let mut text = egui::RichText::new(current.unwrap_or("(none)"));
+    if !valid {
+        text = text.color(ui.visuals().error_fg_color);
+    }
+
+    egui::ComboBox::from_id_salt(id_salt)
+        .selected_text(text)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(value, None, "(none)");
+            for choice in choices {
+                ui.selectable_value(value, Some(choice.clone()), choice.as_str());
+            }
+        });
+
+    if !valid {
+        ui.colored_label(ui.visuals().error_fg_color, "Unknown");
+    }
+}
+
+// Picker for success/failure links, which can also create the dialogue a link should point at
+fn dialogue_picker(ui: &mut egui::Ui, id_salt: impl std::hash::Hash + Copy, value: &mut Option<String>, dialogue_ids: &[String]) -> Option<String> {
+    let mut create = None;
+    choice_picker(ui, id_salt, value, dialogue_ids);
+
+    let dangling = value
+        .as_deref()
+        .filter(|v| !v.is_empty() && !dialogue_ids.iter().any(|id| id == v))
+        .map(str::to_string);
+
+    if let Some(missing) = dangling {
+        // Links into other conversations have to be created in that conversation's tab
+        if !missing.contains('/') && ui.button(format!("Create {}", missing)).clicked() {
+            create = Some(missing);
+        }
+    } else {
+        // Name for a brand new dialogue, kept in egui's memory between frames
+        let new_id_key = egui::Id::new((id_salt, "new_dialogue_id"));
+        let mut new_id = ui.data_mut(|data| data.get_temp::<String>(new_id_key)).unwrap_or_default();
+        ui.add(egui::TextEdit::singleline(&mut new_id).hint_text("new ID").desired_width(100.0));
+        let available = !new_id.is_empty() && !new_id.contains('/') && !dialogue_ids.contains(&new_id);
+        if ui.add_enabled(available, egui::Button::new("Create & Link")).clicked() {
+            *value = Some(new_id.clone());
+            create = Some(std::mem::take(&mut new_id));
+        }
+        ui.data_mut(|data| data.insert_temp(new_id_key, new_id));
+    }
+
+    create
+}
+
+
+pub fn edit_dialogue(
+    ui: &mut egui::Ui,
+    current_id: &str,
+    dialogue: &mut Dialogue,
+    temp_id: &mut String,
+    catalogue: &EditorCatalogue,
+) -> Option<DialogueEditAction> {
     ui.heading(format!("Editing Dialogue: {}", current_id));
 
     // Edit ID (Temporary Field)
@@ -96,17 +587,17 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
     });
 
     // Check if the ID has changed and confirm the update
-    let mut id_changed = false;
-    if *temp_id != current_id && !temp_id.is_empty() {
-        if ui.button("Update ID").clicked() {
-            id_changed = true;
-        }
+    let mut action = None;
+    if *temp_id != current_id && !temp_id.is_empty() && ui.button("Update ID").clicked() {
+        action = Some(DialogueEditAction::Rename(temp_id.clone()));
     }
 
-    // Edit Speaker
+    // Edit Speaker, none meaning narration
     ui.horizontal(|ui| {
         ui.label("Speaker:");
-        ui.text_edit_singleline(&mut dialogue.speaker);
+        let mut speaker = Some(dialogue.speaker.clone()).filter(|id| !id.is_empty());
+        choice_picker(ui, "speaker", &mut speaker, &catalogue.speakers);
+        dialogue.speaker = speaker.unwrap_or_default();
     });
 
     // Edit Intro Text
@@ -115,6 +606,16 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
         ui.text_edit_multiline(&mut dialogue.intro);
     });
 
+    // Images, relative to the project folder; unset falls back to the location's and speaker's
+    ui.horizontal(|ui| {
+        ui.label("Background:");
+        optional_text(ui, &mut dialogue.background, false);
+    });
+    ui.horizontal(|ui| {
+        ui.label("Portrait:");
+        optional_text(ui, &mut dialogue.portrait, false);
+    });
+
     // Edit XP Reward
     ui.horizontal(|ui| {
         ui.label("XP Reward:");
@@ -143,8 +644,14 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
 
     // Edit Options
     ui.label("Options:");
-    for option in &mut dialogue.options {
-        ui.group(|ui| {
+    let option_count = dialogue.options.len();
+    let mut option_action = None;
+    for (index, option) in dialogue.options.iter_mut().enumerate() {
+        let group = ui.group(|ui| {
+            if let Some(action) = list_item_controls(ui, "options", index, option_count) {
+                option_action = Some(action);
+            }
+
             ui.horizontal(|ui| {
                 ui.label("Description:");
                 ui.text_edit_singleline(&mut option.description);
@@ -152,17 +659,21 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
 
             ui.horizontal(|ui| {
                 ui.label("Success Dialogue:");
-                ui.text_edit_multiline(option.success_dialogue.get_or_insert_with(String::new));
+                if let Some(id) = dialogue_picker(ui, ("success_dialogue", index), &mut option.success_dialogue, &catalogue.dialogue_ids) {
+                    action = Some(DialogueEditAction::CreateDialogue(id));
+                }
             });
 
             ui.horizontal(|ui| {
                 ui.label("Failure Dialogue:");
-                ui.text_edit_multiline(option.failure_dialogue.get_or_insert_with(String::new));
+                if let Some(id) = dialogue_picker(ui, ("failure_dialogue", index), &mut option.failure_dialogue, &catalogue.dialogue_ids) {
+                    action = Some(DialogueEditAction::CreateDialogue(id));
+                }
             });
 
             ui.horizontal(|ui| {
                 ui.label("Challenge Attribute:");
-                ui.text_edit_singleline(option.challenge_attribute.get_or_insert_with(String::new));
+                choice_picker(ui, ("challenge_attribute", index), &mut option.challenge_attribute, &catalogue.skills);
             });
 
             ui.horizontal(|ui| {
@@ -176,14 +687,25 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
 
             ui.horizontal(|ui| {
                 ui.label("Item to Pick Up:");
-                ui.text_edit_singleline(option.item_to_pickup.get_or_insert_with(String::new));
+                choice_picker(ui, ("item_to_pickup", index), &mut option.item_to_pickup, &catalogue.items);
             });
 
             ui.horizontal(|ui| {
                 ui.label("Visible When:");
-                ui.text_edit_singleline(option.visible_when.get_or_insert_with(String::new));
+                optional_text(ui, &mut option.visible_when, false);
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Move To Location:");
+                choice_picker(ui, ("move_to", index), &mut option.move_to, &catalogue.locations);
             });
         });
+        if let Some(action) = list_drop_target(&group.response, "options", index) {
+            option_action = Some(action);
+        }
+    }
+    if let Some(action) = option_action {
+        apply_list_action(&mut dialogue.options, action);
     }
 
     if ui.button("Add Option").clicked() {
@@ -192,11 +714,19 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
 
     // Edit Passive Checks
     ui.label("Passive Checks:");
-    for check in &mut dialogue.passive_check {
-        ui.group(|ui| {
+    let check_count = dialogue.passive_check.len();
+    let mut check_action = None;
+    for (index, check) in dialogue.passive_check.iter_mut().enumerate() {
+        let group = ui.group(|ui| {
+            if let Some(action) = list_item_controls(ui, "passive_check", index, check_count) {
+                check_action = Some(action);
+            }
+
             ui.horizontal(|ui| {
                 ui.label("Skill:");
-                ui.text_edit_singleline(&mut check.skill);
+                let mut skill = Some(check.skill.clone());
+                choice_picker(ui, ("passive_check_skill", index), &mut skill, &catalogue.skills);
+                check.skill = skill.unwrap_or_default();
             });
 
             ui.horizontal(|ui| {
@@ -206,56 +736,305 @@ pub fn edit_dialogue(ui: &mut egui::Ui, current_id: &str, dialogue: &mut Dialogu
 
             ui.horizontal(|ui| {
                 ui.label("Success Text:");
-                ui.text_edit_multiline(check.success_text.get_or_insert_with(String::new));
+                optional_text(ui, &mut check.success_text, true);
             });
 
             ui.horizontal(|ui| {
                 ui.label("Failure Text:");
-                ui.text_edit_multiline(check.failure_text.get_or_insert_with(String::new));
+                optional_text(ui, &mut check.failure_text, true);
             });
 
             ui.horizontal(|ui| {
                 ui.label("Speaker:");
-                ui.text_edit_singleline(check.speaker.get_or_insert_with(String::new));
+                choice_picker(ui, ("check_speaker", index), &mut check.speaker, &catalogue.speakers);
             });
         });
+        if let Some(action) = list_drop_target(&group.response, "passive_check", index) {
+            check_action = Some(action);
+        }
+    }
+    if let Some(action) = check_action {
+        apply_list_action(&mut dialogue.passive_check, action);
     }
 
     if ui.button("Add Passive Check").clicked() {
         dialogue.passive_check.push(PassiveCheck::default());
     }
 
-    if id_changed {
-        Some(temp_id.clone())
-    } else {
-        None
+    action
+}
+
+// Changes to the project's list of locations, carried out by the caller
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocationEditAction {
+    Rename(String),
+    Remove,
+    OpenConversation(String),
+}
+
+// Conversations and exits of one location in the project file
+// location_names is every location an exit may lead to; temp_name holds the rename field
+pub fn edit_location(
+    ui: &mut egui::Ui,
+    name: &str,
+    location: &mut project::LocationEntry,
+    temp_name: &mut String,
+    location_names: &[String],
+) -> Option<LocationEditAction> {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        ui.text_edit_singleline(temp_name);
+        if *temp_name != name && !temp_name.is_empty() && ui.button("Rename").clicked() {
+            action = Some(LocationEditAction::Rename(temp_name.clone()));
+        }
+        if ui.button("Delete Location").clicked() {
+            action = Some(LocationEditAction::Remove);
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Background:");
+        optional_text(ui, &mut location.background, false);
+    });
+
+    ui.label("Conversations (dialogue files are relative to the project file):");
+    let mut remove_conversation = None;
+    for (conversation, file) in location.conversations.iter_mut() {
+        ui.horizontal(|ui| {
+            ui.label(conversation);
+            ui.text_edit_singleline(file);
+            if ui.button("Open").clicked() {
+                action = Some(LocationEditAction::OpenConversation(conversation.clone()));
+            }
+            if ui.button("Remove").clicked() {
+                remove_conversation = Some(conversation.clone());
+            }
+        });
+    }
+    if let Some(conversation) = remove_conversation {
+        location.conversations.shift_remove(&conversation);
+    }
+
+    // New conversations get a file named after them until the author picks another
+    let new_id = egui::Id::new((name, "new_conversation"));
+    let mut new_conversation = ui.data_mut(|data| data.get_temp::<String>(new_id)).unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut new_conversation);
+        let valid = !new_conversation.is_empty()
+            && !new_conversation.contains('/')
+            && !location.conversations.contains_key(&new_conversation);
+        if ui.add_enabled(valid, egui::Button::new("Attach Conversation")).clicked() {
+            let file = format!("dialogues/{}.json", new_conversation.to_lowercase());
+            location.conversations.insert(std::mem::take(&mut new_conversation), file);
+        }
+    });
+    ui.data_mut(|data| data.insert_temp(new_id, new_conversation));
+
+    ui.label("Exits:");
+    let mut exit_action = None;
+    let len = location.exits.len();
+    for (i, exit) in location.exits.iter_mut().enumerate() {
+        let response = ui
+            .group(|ui| {
+                if let Some(action) = list_item_controls(ui, "exits", i, len) {
+                    exit_action = Some(action);
+                }
+                ui.horizontal(|ui| {
+                    ui.label("To:");
+                    let mut to = Some(exit.to.clone()).filter(|to| !to.is_empty());
+                    choice_picker(ui, (name, "exit", i), &mut to, location_names);
+                    exit.to = to.unwrap_or_default();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Condition:");
+                    optional_text(ui, &mut exit.condition, false);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Time Cost:");
+                    if let Some(minutes) = &mut exit.time_cost {
+                        ui.add(egui::DragValue::new(minutes).range(0..=i32::MAX).suffix(" min"));
+                        if ui.button("Unset").clicked() {
+                            exit.time_cost = None;
+                        }
+                    } else if ui.button("Add Time Cost").clicked() {
+                        exit.time_cost = Some(0);
+                    }
+                });
+            })
+            .response;
+        if let Some(action) = list_drop_target(&response, "exits", i) {
+            exit_action = Some(action);
+        }
+    }
+    if let Some(action) = exit_action {
+        apply_list_action(&mut location.exits, action);
+    }
+
+    if ui.button("Add Exit").clicked() {
+        location.exits.push(Exit::default());
     }
+
+    action
 }
 
 
 
-fn _view_dialogues(dialogues: &HashMap<String, Dialogue>) {
+// One entry of the speaker catalogue; returns true when the user asks to remove it
+pub fn edit_speaker(ui: &mut egui::Ui, id: &str, speaker: &mut speakers::Speaker) -> bool {
+    let mut remove = false;
+
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        ui.text_edit_singleline(&mut speaker.name);
+        if ui.button("Delete Speaker").clicked() {
+            remove = true;
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Kind:");
+        egui::ComboBox::from_id_salt((id, "speaker_kind"))
+            .selected_text(format!("{:?}", speaker.kind))
+            .show_ui(ui, |ui| {
+                for kind in [speakers::SpeakerKind::Character, speakers::SpeakerKind::Skill, speakers::SpeakerKind::Narrator] {
+                    ui.selectable_value(&mut speaker.kind, kind, format!("{:?}", kind));
+                }
+            });
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Text Color:");
+        match speaker.text_color() {
+            Some(color) => {
+                let mut rgb = [color.r(), color.g(), color.b()];
+                if ui.color_edit_button_srgb(&mut rgb).changed() {
+                    speaker.color = Some(format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]));
+                }
+                if ui.button("Unset").clicked() {
+                    speaker.color = None;
+                }
+            }
+            None => {
+                if speaker.color.is_some() {
+                    ui.colored_label(ui.visuals().error_fg_color, "Not a #rrggbb color");
+                }
+                if ui.button("Set Color").clicked() {
+                    speaker.color = Some("#ffffff".to_string());
+                }
+            }
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Portrait:");
+        optional_text(ui, &mut speaker.portrait, false);
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Preview:");
+        match speakers::speaker_label(&speakers::Speakers::from([(id.to_string(), speaker.clone())]), id) {
+            Some(label) => ui.label(label),
+            None => ui.weak("(narration shows no name)"),
+        };
+    });
+
+    remove
+}
+
+fn _view_dialogues(dialogues: &Dialogues) {
     for (id, dialogue) in dialogues {
         println!("ID: {}\nSpeaker: {}\nIntro: {}\n", id, dialogue.speaker, dialogue.intro);
     }
 }
 
-pub fn save_to_file(dialogues: &HashMap<String, Dialogue>, file_path: String) {
-    // Serialize the current dialogues
-    let json = serde_json::to_string_pretty(&dialogues).expect("Failed to serialize dialogues");
-
-    // Write the serialized data to the file, overwriting its contents
-    std::fs::write(&file_path, json).expect("Failed to write to file");
+pub fn save_to_file(dialogues: &Dialogues, file_path: String) -> Result<(), io::Error> {
+    // Never write "" for a field that is unset
+    let mut dialogues = dialogues.clone();
+    normalize_dialogues(&mut dialogues);
+    write_dialogues(&dialogues, &file_path)?;
 
     println!("Dialogues saved successfully to {}", file_path);
+    Ok(())
+}
+
+// Writes dialogues in their authoring order, so saving the same content always produces the same file
+// The JSON goes to a temporary file first and is renamed over the original, so a crash can't truncate it
+fn write_dialogues(dialogues: &Dialogues, file_path: &str) -> Result<(), io::Error> {
+    let mut json = serde_json::to_string_pretty(dialogues)?;
+    json.push('\n');
+
+    let path = Path::new(file_path);
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    {
+        let mut file = fs::File::create(&temp_path)?;
+        io::Write::write_all(&mut file, json.as_bytes())?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, path)
+}
+
+// Where the editor autosaves unsaved work for a dialogue file
+pub fn recovery_path(file_path: &str) -> String {
+    format!("{}.recovery", file_path)
 }
 
-pub fn initialize_dialogues(file_path: &str) -> Result<(HashMap<String, Dialogue>, String), io::Error>{
+pub fn save_recovery(dialogues: &Dialogues, file_path: &str) -> Result<(), io::Error> {
+    write_dialogues(dialogues, &recovery_path(file_path))
+}
+
+// Three-way merge of a file edited in two places, one dialogue at a time
+// base is the file both sides started from; where both changed the same dialogue, mine wins
+// Returns the merged dialogues and the IDs that were changed on both sides
+pub fn merge_dialogues(base: &Dialogues, mine: &Dialogues, theirs: &Dialogues) -> (Dialogues, Vec<String>) {
+    let mut merged = theirs.clone();
+    let mut conflicts = vec![];
+
+    for (id, dialogue) in mine {
+        let original = base.get(id);
+        if original == Some(dialogue) {
+            continue; // Untouched on my side, whatever they did stands
+        }
+        let their_version = theirs.get(id);
+        if their_version != original && their_version != Some(dialogue) {
+            conflicts.push(id.clone());
+        }
+        merged.insert(id.clone(), dialogue.clone());
+    }
+
+    for (id, original) in base {
+        if mine.contains_key(id) {
+            continue;
+        }
+        // Deleted on my side
+        if theirs.get(id).is_some_and(|their_version| their_version != original) {
+            conflicts.push(id.clone());
+        }
+        merged.shift_remove(id);
+    }
+
+    (merged, conflicts)
+}
+
+pub fn initialize_dialogues(file_path: &str) -> Result<(Dialogues, String), io::Error>{
     // Attempt to load dialogues from the file
     let dialogues = if let Ok(content) = std::fs::read_to_string(file_path) {
-        serde_json::from_str::<HashMap<String, Dialogue>>(&content).unwrap_or_default()
+        match parse_dialogues(&content, Strictness::Lenient) {
+            Ok(loaded) => {
+                report_load_issues(file_path, &loaded);
+                loaded.dialogues
+            }
+            Err(err) => {
+                eprintln!("Error parsing dialogues in {}: {}", file_path, err);
+                Dialogues::new()
+            }
+        }
     } else {
-        HashMap::new() // Start with an empty HashMap if the file doesn't exist
+        Dialogues::new() // Start with an empty map if the file doesn't exist
     };
 
     Ok(
@@ -271,7 +1050,7 @@ pub fn initialize_dialogues(file_path: &str) -> Result<(HashMap<String, Dialogue
 #[derive(Clone, PartialEq)]
 pub struct Conversation {
     pub name: String,
-    pub dialogues: HashMap<String, Dialogue>,
+    pub dialogues: Dialogues,
 
 }
 
@@ -279,39 +1058,77 @@ pub struct Conversation {
 pub struct Location {
     pub name: String,
     pub conversations: HashMap<String, Conversation>,
-    pub exits: Vec<String>, // Names of other locations you can move to
+    pub exits: Vec<Exit>,
+    pub background: Option<String>, // Image path relative to the project folder
+}
+
+// A way out of a location; unset fields are left out of the project file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Exit {
+    pub to: String, // Name of the location it leads to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>, // Flag or item the player needs before the exit is offered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_cost: Option<i32>, // Minutes it takes to get there
 }
 
+// Unset fields are left out of saved files; a missing field reads back as unset
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct DialogueOption {
     pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub challenge_attribute: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub challenge_number: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub success_dialogue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub failure_dialogue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub item_to_pickup: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub visible_when: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub flags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub move_to: Option<String>, // Location the player walks to when choosing this option
 
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Dialogue {
-    pub speaker: String,
+    #[serde(default)]
+    pub speaker: String, // Empty for narration
     pub intro: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub options: Vec<DialogueOption>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub passive_check: Vec<PassiveCheck>, // New field for passive dialogue checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub xp_reward: Option<i32>,
+    #[serde(default, skip_serializing_if = "is_false")]
     pub is_hidden: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub time: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>, // Image path relative to the project folder, replacing the location's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portrait: Option<String>, // Image path relative to the project folder, replacing the speaker's
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct PassiveCheck {
     pub skill: String,          // The player's skill to check
     pub target: i32,            // The number to check against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub success_text: Option<String>, // Text to display on success (Optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub failure_text: Option<String>, // Text to display on failure (Optional)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub speaker: Option<String>, // The speaker, who will be the same in both success and failure cases
 }
 
@@ -339,6 +1156,7 @@ impl Default for DialogueOption {
             item_to_pickup: None,
             visible_when: None,
             flags: None,
+            move_to: None,
         }
     }
 }
@@ -355,6 +1173,8 @@ impl Default for Dialogue {
             xp_reward: None,
             is_hidden: true,
             time: Some(1),
+            background: None,
+            portrait: None,
         }
     }
 }
@@ -365,6 +1185,7 @@ impl Location {
             name,
             conversations: HashMap::new(),
             exits: vec![],
+            background: None,
         }
     }
 
@@ -372,7 +1193,7 @@ impl Location {
         self.conversations.insert(id, conversation);
     }
 
-    pub fn add_exit(&mut self, exit: String) {
+    pub fn add_exit(&mut self, exit: Exit) {
         self.exits.push(exit);
     }
 }
@@ -381,7 +1202,7 @@ impl Conversation {
     pub fn new(name: String) -> Self {
         Self {
             name, 
-            dialogues: HashMap::new(),
+            dialogues: Dialogues::new(),
         }
     }
 
@@ -389,6 +1210,10 @@ impl Conversation {
         self.dialogues.insert(id, dialogue);
     }
 
+    pub fn rename_dialogue(&mut self, old_id: &str, new_id: &str) -> Result<usize, RenameError> {
+        rename_dialogue(&mut self.dialogues, old_id, new_id)
+    }
+
 }
 //create defaults and use them, reduce space taken up
 
@@ -449,3 +1274,109 @@ pub fn create_locations() -> HashMap<String, Location> {
     
     locations
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linking(id: &str, links: &[&str]) -> (String, Dialogue) {
+        let options = links
+            .iter()
+            .map(|link| DialogueOption {
+                success_dialogue: Some(link.to_string()),
+                ..Default::default()
+            })
+            .collect();
+        (id.to_string(), Dialogue { options, ..Default::default() })
+    }
+
+    #[test]
+    fn defaults_keep_links_as_written() {
+        let content = r#"{
+            "Start": { "speaker": "", "intro": "Tick", "options": [{ "description": "Bye", "success_dialogue": "" }],
+                "passive_check": [{ "skill": "Logic", "target": 8, "success_text": "It's slow" }] },
+            "End": { "speaker": "", "intro": "Tock", "options": [] }
+        }"#;
+        let mut dialogues = parse_dialogues(content, Strictness::Lenient).unwrap().dialogues;
+        dialogues.values_mut().for_each(validate_and_fill_defaults);
+
+        assert_eq!(dialogues["Start"].options[0].success_dialogue, None);
+        assert_eq!(dialogues["Start"].passive_check.len(), 1);
+        assert_eq!(dialogues["End"].options.len(), 1);
+        assert_eq!(dialogues["End"].options[0].description, "Continue");
+        assert_eq!(dialogues["End"].options[0].success_dialogue, None);
+    }
+
+    fn said(text: &str) -> Dialogue {
+        Dialogue {
+            intro: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn file(entries: &[(&str, &str)]) -> Dialogues {
+        entries.iter().map(|(id, text)| (id.to_string(), said(text))).collect()
+    }
+
+    #[test]
+    fn merge_keeps_both_sides_changes() {
+        let base = file(&[("Start", "Hi"), ("Clock", "Tick"), ("Door", "Creak")]);
+        let mine = file(&[("Start", "Hello"), ("Clock", "Tick"), ("Door", "Creak"), ("Mine", "New")]);
+        let theirs = file(&[("Start", "Hi"), ("Clock", "Tock"), ("Theirs", "Also new")]);
+
+        let (merged, conflicts) = merge_dialogues(&base, &mine, &theirs);
+        assert!(conflicts.is_empty());
+        assert_eq!(merged, file(&[("Start", "Hello"), ("Clock", "Tock"), ("Theirs", "Also new"), ("Mine", "New")]));
+    }
+
+    #[test]
+    fn merge_reports_conflicts_and_keeps_mine() {
+        let base = file(&[("Start", "Hi"), ("Clock", "Tick")]);
+        let mine = file(&[("Start", "Hello"), ("Clock", "Tick")]);
+        let theirs = file(&[("Start", "Howdy"), ("Clock", "Tick")]);
+        let (merged, conflicts) = merge_dialogues(&base, &mine, &theirs);
+        assert_eq!(conflicts, vec!["Start"]);
+        assert_eq!(merged["Start"].intro, "Hello");
+
+        // The same edit on both sides isn't a conflict
+        let (_, conflicts) = merge_dialogues(&base, &mine, &mine);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn merge_flags_deletes_against_edits() {
+        let base = file(&[("Start", "Hi"), ("Clock", "Tick")]);
+
+        // I deleted what they edited: the delete wins, but it's a conflict
+        let (merged, conflicts) = merge_dialogues(&base, &file(&[("Start", "Hi")]), &file(&[("Start", "Hi"), ("Clock", "Tock")]));
+        assert_eq!(conflicts, vec!["Clock"]);
+        assert!(!merged.contains_key("Clock"));
+
+        // I edited what they deleted: my edit comes back, also a conflict
+        let (merged, conflicts) = merge_dialogues(&base, &file(&[("Start", "Hi"), ("Clock", "Tock")]), &file(&[("Start", "Hi")]));
+        assert_eq!(conflicts, vec!["Clock"]);
+        assert_eq!(merged["Clock"].intro, "Tock");
+
+        // Deleted on both sides, or deleted by them and untouched by me, stays deleted
+        let (merged, conflicts) = merge_dialogues(&base, &file(&[("Start", "Hi")]), &file(&[("Start", "Hi")]));
+        assert!(conflicts.is_empty() && !merged.contains_key("Clock"));
+        let (merged, conflicts) = merge_dialogues(&base, &base, &file(&[("Start", "Hi")]));
+        assert!(conflicts.is_empty() && !merged.contains_key("Clock"));
+    }
+
+    #[test]
+    fn project_rename_leaves_bare_ids_of_other_files_alone() {
+        let mut clock: Dialogues = [linking("Start", &["Old", "Clock/Old"]), linking("Old", &["Old"])].into_iter().collect();
+        let mut intro: Dialogues = [linking("Start", &["Old", "Clock/Old"]), linking("Old", &[])].into_iter().collect();
+
+        let rewritten = rename_dialogue_in_project(&mut clock, "Clock", &mut [&mut intro], "Old", "New").unwrap();
+        assert_eq!(rewritten, 4);
+        assert_eq!(clock.keys().collect::<Vec<_>>(), vec!["Start", "New"]);
+        let targets = |dialogues: &Dialogues, id: &str| -> Vec<String> {
+            dialogues[id].options.iter().filter_map(|option| option.success_dialogue.clone()).collect()
+        };
+        assert_eq!(targets(&clock, "Start"), vec!["New", "Clock/New"]);
+        assert_eq!(targets(&clock, "New"), vec!["New"]);
+        assert_eq!(targets(&intro, "Start"), vec!["Old", "Clock/New"]);
+    }
+}
@@ -0,0 +1,143 @@
+// Reading dialogue files one dialogue at a time, so a mistake in one dialogue doesn't cost the whole file
+// Fields the structs don't know, usually typos like "sucess_dialogue", are reported against the dialogue
+// and field they're in: as warnings normally, or as errors that keep the dialogue out in strict mode
+// A dialogue that can't be read at all is left out with an error naming what's wrong with it
+
+use std::fmt;
+use std::fs;
+use std::io;
+
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::schema::dialogue_violations;
+use crate::{normalize_dialogue, Dialogue, Dialogues};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Strictness {
+    #[default]
+    Lenient, // Unknown fields are warnings and the dialogue loads without them
+    Strict,  // Unknown fields are errors and the dialogue is left out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadIssue {
+    pub severity: Severity,
+    pub dialogue: String,
+    pub field: Option<String>, // Path inside the dialogue, like options[0].sucess_dialogue
+    pub message: String,
+}
+
+impl fmt::Display for LoadIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match &self.field {
+            Some(field) => write!(f, "{}: {}: {}: {}", severity, self.dialogue, field, self.message),
+            None => write!(f, "{}: {}: {}", severity, self.dialogue, self.message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Loaded {
+    pub dialogues: Dialogues, // Everything that could be read, with empty strings cleared
+    pub issues: Vec<LoadIssue>,
+}
+
+impl Loaded {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|issue| issue.severity == Severity::Error)
+    }
+}
+
+// Fails only when the text isn't a JSON object of dialogues at all
+pub fn parse_dialogues(content: &str, strictness: Strictness) -> Result<Loaded, serde_json::Error> {
+    let entries: IndexMap<String, Value> = serde_json::from_str(content)?;
+    let mut loaded = Loaded::default();
+
+    for (id, value) in entries {
+        let violations = dialogue_violations(&value);
+        let issue = |severity, field: &str, message: &str| LoadIssue {
+            severity,
+            dialogue: id.clone(),
+            field: (!field.is_empty()).then(|| field.to_string()),
+            message: message.to_string(),
+        };
+
+        let unknown_severity = match strictness {
+            Strictness::Lenient => Severity::Warning,
+            Strictness::Strict => Severity::Error,
+        };
+        for violation in violations.iter().filter(|violation| violation.unknown_field) {
+            loaded.issues.push(issue(unknown_severity, &violation.path, &violation.message));
+        }
+
+        match serde_json::from_value::<Dialogue>(value) {
+            Ok(mut dialogue) => {
+                if unknown_severity == Severity::Error && violations.iter().any(|violation| violation.unknown_field) {
+                    continue;
+                }
+                normalize_dialogue(&mut dialogue);
+                loaded.dialogues.insert(id, dialogue);
+            }
+            Err(err) => {
+                // The schema's paths say which field is wrong; serde's message is the fallback
+                let explained: Vec<_> = violations.iter().filter(|violation| !violation.unknown_field).collect();
+                if explained.is_empty() {
+                    loaded.issues.push(issue(Severity::Error, "", &err.to_string()));
+                }
+                for violation in explained {
+                    loaded.issues.push(issue(Severity::Error, &violation.path, &violation.message));
+                }
+            }
+        }
+    }
+    Ok(loaded)
+}
+
+pub fn load_dialogue_file(file_path: &str, strictness: Strictness) -> Result<Loaded, io::Error> {
+    let content = fs::read_to_string(file_path)?;
+    Ok(parse_dialogues(&content, strictness)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"{
+        "Start": { "intro": "Hi", "options": [{ "description": "Go", "sucess_dialogue": "Next" }] },
+        "Broken": { "speaker": "Clock", "intro": "Tick", "xp_reward": "lots" },
+        "Next": { "speaker": "Clock", "intro": "Bye", "options": [{ "description": "Leave", "flags": null }] }
+    }"#;
+
+    #[test]
+    fn one_bad_dialogue_keeps_the_rest() {
+        let loaded = parse_dialogues(FILE, Strictness::Lenient).unwrap();
+        assert_eq!(loaded.dialogues.keys().collect::<Vec<_>>(), vec!["Start", "Next"]);
+        assert_eq!(loaded.dialogues["Start"].speaker, "");
+        assert_eq!(loaded.issues.len(), 2);
+        assert_eq!(
+            loaded.issues[0].to_string(),
+            "warning: Start: options[0].sucess_dialogue: unknown field; did you mean 'success_dialogue'?"
+        );
+        assert_eq!(loaded.issues[1].to_string(), "error: Broken: xp_reward: expected integer or null, found string");
+    }
+
+    #[test]
+    fn strict_mode_rejects_unknown_fields() {
+        let loaded = parse_dialogues(FILE, Strictness::Strict).unwrap();
+        assert_eq!(loaded.dialogues.keys().collect::<Vec<_>>(), vec!["Next"]);
+        assert_eq!(loaded.issues[0].severity, Severity::Error);
+        assert!(loaded.has_errors());
+        assert!(parse_dialogues("[]", Strictness::Lenient).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+// Translations of the dialogue text, one string table per language in the project's locales folder
+// The dialogue files stay the source language; a table maps each text field to its translation,
+// keyed "Conversation/DialogueId/field", with field as in markup::labelled_texts (intro, options[0].description, ...)
+//
+// Each entry keeps the source text it was translated from, so a translation whose source has since
+// changed can be reported as stale. Option keys use the option's position, so reordering options
+// shows up as stale strings too. The game only shows a translation made from the text it's showing,
+// so a moved option never gets another option's translation
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::markup::{labelled_texts, Markup};
+use crate::project::Project;
+use crate::{Dialogue, Dialogues};
+
+pub const LOCALE_DIR: &str = "locales";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct TranslatedString {
+    pub source: String, // The source text when this was translated
+    #[serde(default)]
+    pub text: String, // Empty until someone translates it
+}
+
+// Translations by string key, in the order the project's text is written
+pub type StringTable = IndexMap<String, TranslatedString>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StringStatus {
+    Untranslated,
+    Stale,    // The source text changed after it was translated
+    Obsolete, // The field it belonged to is gone
+}
+
+pub fn string_key(conversation: &str, dialogue: &str, field: &str) -> String {
+    format!("{}/{}/{}", conversation, dialogue, field)
+}
+
+// Every non-empty text of every conversation by key, in project order
+pub fn source_strings(project: &Project, project_path: &str, conversations: &HashMap<String, Dialogues>) -> IndexMap<String, String> {
+    let mut strings = IndexMap::new();
+    for file in project.conversation_files(project_path) {
+        let Some(dialogues) = conversations.get(&file.conversation) else {
+            continue;
+        };
+        for (id, dialogue) in dialogues {
+            for (field, text) in labelled_texts(dialogue) {
+                if !text.is_empty() {
+                    strings.insert(string_key(&file.conversation, id, &field), text.to_string());
+                }
+            }
+        }
+    }
+    strings
+}
+
+pub fn locale_path(project_path: &str, language: &str) -> PathBuf {
+    Project::base_dir(project_path).join(LOCALE_DIR).join(format!("{}.json", language))
+}
+
+// Languages with a table next to the project, sorted
+pub fn languages(project_path: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(Project::base_dir(project_path).join(LOCALE_DIR)) else {
+        return vec![];
+    };
+    let mut languages: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .collect();
+    languages.sort();
+    languages
+}
+
+// A language nobody has started on yet has an empty table
+pub fn load_table(project_path: &str, language: &str) -> Result<StringTable, io::Error> {
+    match fs::read_to_string(locale_path(project_path, language)) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(StringTable::new()),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn save_table(table: &StringTable, project_path: &str, language: &str) -> Result<(), io::Error> {
+    let path = locale_path(project_path, language);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut json = serde_json::to_string_pretty(table)?;
+    json.push('\n');
+    fs::write(path, json)
+}
+
+// Adds an untranslated entry for every source string the table doesn't have; returns how many were added
+// Existing entries keep the source they were translated from, and entries for removed fields stay put,
+// so neither a stale nor an obsolete translation is lost by extracting again
+pub fn extract(table: &mut StringTable, sources: &IndexMap<String, String>) -> usize {
+    let mut added = 0;
+    for (key, source) in sources {
+        if !table.contains_key(key) {
+            table.insert(
+                key.clone(),
+                TranslatedString {
+                    source: source.clone(),
+                    text: String::new(),
+                },
+            );
+            added += 1;
+        }
+    }
+    // Keep the table in project order, with obsolete entries at the end
+    table.sort_by_cached_key(|key, _| sources.get_index_of(key).unwrap_or(usize::MAX));
+    added
+}
+
+// Every key that needs a translator's attention, in project order, then the obsolete ones
+pub fn report<'a>(table: &'a StringTable, sources: &'a IndexMap<String, String>) -> Vec<(&'a str, StringStatus)> {
+    let mut statuses = vec![];
+    for (key, source) in sources {
+        match table.get(key) {
+            Some(entry) if entry.text.is_empty() => statuses.push((key.as_str(), StringStatus::Untranslated)),
+            Some(entry) if entry.source != *source => statuses.push((key.as_str(), StringStatus::Stale)),
+            Some(_) => {}
+            None => statuses.push((key.as_str(), StringStatus::Untranslated)),
+        }
+    }
+    for key in table.keys().filter(|key| !sources.contains_key(*key)) {
+        statuses.push((key.as_str(), StringStatus::Obsolete));
+    }
+    statuses
+}
+
+// Translations whose markup doesn't parse, with the error
+pub fn markup_errors(table: &StringTable) -> Vec<(&str, String)> {
+    table
+        .iter()
+        .filter(|(_, entry)| !entry.text.is_empty())
+        .filter_map(|(key, entry)| Markup::parse(&entry.text).err().map(|err| (key.as_str(), err.to_string())))
+        .collect()
+}
+
+// The language the game is being played in
+pub struct Language {
+    pub name: String,
+    pub table: StringTable,
+}
+
+impl Language {
+    pub fn load(project_path: &str, name: &str) -> Result<Self, io::Error> {
+        Ok(Self {
+            name: name.to_string(),
+            table: load_table(project_path, name)?,
+        })
+    }
+
+    // The translation of a field, or the source text if it hasn't been translated
+    // A stale entry may belong to different text by now, like an option that moved, so it falls back too
+    pub fn text<'a>(&'a self, key: &str, source: &'a str) -> &'a str {
+        match self.table.get(key) {
+            Some(entry) if !entry.text.is_empty() && entry.source == source => &entry.text,
+            _ => source,
+        }
+    }
+}
+
+// The dialogue with its text in the player's language; with no language set it's the dialogue as written
+pub fn localize<'a>(language: Option<&Language>, conversation: &str, id: &str, dialogue: &'a Dialogue) -> Cow<'a, Dialogue> {
+    let Some(language) = language else {
+        return Cow::Borrowed(dialogue);
+    };
+    let text = |field: &str, source: &str| language.text(&string_key(conversation, id, field), source).to_string();
+
+    let mut localized = dialogue.clone();
+    localized.intro = text("intro", &dialogue.intro);
+    for (i, option) in localized.options.iter_mut().enumerate() {
+        option.description = text(&format!("options[{}].description", i), &option.description);
+    }
+    for (i, check) in localized.passive_check.iter_mut().enumerate() {
+        if let Some(success) = &check.success_text {
+            check.success_text = Some(text(&format!("passive_check[{}].success_text", i), success));
+        }
+        if let Some(failure) = &check.failure_text {
+            check.failure_text = Some(text(&format!("passive_check[{}].failure_text", i), failure));
+        }
+    }
+    Cow::Owned(localized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DialogueOption;
+
+    #[test]
+    fn stale_translations_are_not_shown() {
+        let entry = |source: &str, text: &str| TranslatedString {
+            source: source.to_string(),
+            text: text.to_string(),
+        };
+        let language = Language {
+            name: "de".to_string(),
+            table: [
+                ("Clock/Start/intro".to_string(), entry("Tick.", "Tick.")),
+                ("Clock/Start/options[0].description".to_string(), entry("Leave", "Gehen")),
+                ("Clock/Start/options[1].description".to_string(), entry("Stay", "Bleiben")),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        // The options were swapped since the table was written
+        let option = |description: &str| DialogueOption {
+            description: description.to_string(),
+            ..Default::default()
+        };
+        let dialogue = Dialogue {
+            intro: "Tock.".to_string(),
+            options: vec![option("Stay"), option("Leave")],
+            ..Default::default()
+        };
+        let localized = localize(Some(&language), "Clock", "Start", &dialogue);
+        assert_eq!(localized.intro, "Tock.");
+        assert_eq!(localized.options[0].description, "Stay");
+        assert_eq!(localized.options[1].description, "Leave");
+
+        let dialogue = Dialogue {
+            intro: "Tick.".to_string(),
+            options: vec![option("Leave")],
+            ..Default::default()
+        };
+        assert_eq!(localize(Some(&language), "Clock", "Start", &dialogue).options[0].description, "Gehen");
+    }
+}
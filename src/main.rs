@@ -1,8 +1,30 @@
-use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use clap::{Parser, Subcommand};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
 
 use shadow_soldiers_dialog::*;
+use shadow_soldiers_dialog::exploration::{explore, valid_builds, BuildReport, DEFAULT_STATE_LIMIT};
+use shadow_soldiers_dialog::loading::{load_dialogue_file, Severity, Strictness};
+use shadow_soldiers_dialog::localization::{
+    extract, languages, load_table, markup_errors, report, save_table, source_strings, Language, StringStatus,
+};
+use shadow_soldiers_dialog::markup::{dialogue_texts, layout_job, MarkupCache, MarkupContext};
+use shadow_soldiers_dialog::navigation::{NavigationError, Position};
+use shadow_soldiers_dialog::player::{parse_build, Player};
+use shadow_soldiers_dialog::playtest::{playtest, DEFAULT_MAX_STEPS, DEFAULT_RUNS};
+use shadow_soldiers_dialog::project::{Project, DEFAULT_PROJECT_FILE};
+use shadow_soldiers_dialog::runtime::{Game, Turn};
+use shadow_soldiers_dialog::schema::{project_files, schemas, validate_file, write_schemas};
+use shadow_soldiers_dialog::scene::{show_background, show_dialogue, ImageCache};
+use shadow_soldiers_dialog::stats::project_stats;
+use shadow_soldiers_dialog::transcript::replay;
+use shadow_soldiers_dialog::validation::{completeness_counts, validate_conversations};
+use shadow_soldiers_dialog::speakers::{load_speakers, speaker_label, speaker_path, Speakers};
+mod editor;
+use editor::{DialogueEditorApp, DEFAULT_DIALOGUE_FILE};
 mod isometric;
+mod terminal;
 use isometric::IsometricSpace;
 
 #[allow(unused_imports)]
@@ -21,439 +43,662 @@ enum GameState {
 #[allow(dead_code)]
 struct DialogueApp {
     current_text: String,
-    player: Player,
-    locations: HashMap<String, Location>, // All locations in the game
-    current_location_id: String,          // Current location ID
-    current_dialogue_id: Option<String>,  // Current dialogue ID, or None if not in a dialogue
+    project_path: String,       // Project file the game was loaded from, for its translations
+    game: Game,                 // Where the player is and what they have, shared with the terminal game and transcripts
+    speakers: Speakers,         // Names and colors the dialogue text is shown with
+    images: ImageCache,         // Backgrounds and portraits, loaded from the project folder
+    markup: MarkupCache,        // Dialogue text parsed into styled spans
+    log: Vec<LogEntry>,         // Dialogue shown so far, with the options the player picked
+    languages: Vec<String>,     // Languages the project has string tables for
+    dice: StdRng,               // Seeded with --seed to replay a game roll for roll
     state: GameState,
     previous_dialogue_id: Option<String>,
-    current_time: Time,
     isometric_space: IsometricSpace,
 }
 
-impl Default for DialogueApp {
-    fn default() -> Self {
+struct LogEntry {
+    speaker: String,
+    text: String,
+}
+
+impl DialogueApp {
+    fn new(project_path: &str) -> Self {
+        // The project file names the locations and the files of their conversations
+        let (locations, mut position) = match Project::load(project_path) {
+            Ok(project) => (project.load_locations(project_path), Position::at(&project.start_location)),
+            Err(_) => (create_locations(), Position::at("Vestibule")),
+        };
+        if position.enter_conversation(&locations, "Intro").is_err() {
+            position.dialogue = Some("Start".to_string());
+        }
+
+        // Parse every line of text once, up front, and point out variables the game can't fill in
+        let mut markup = MarkupCache::new();
+        for location in locations.values() {
+            for (conversation_id, conversation) in &location.conversations {
+                for (id, dialogue) in &conversation.dialogues {
+                    markup.add_dialogue(dialogue);
+                    for text in dialogue_texts(dialogue) {
+                        for variable in markup.get(text).unknown_variables() {
+                            eprintln!("Unknown variable '{}' in {}/{}", variable, conversation_id, id);
+                        }
+                    }
+                }
+            }
+        }
+        // Start with the "Start" dialogue of the Vestibule intro
+        let mut game = Game::new(locations, position, Player::with_build(3, 3, 3, 3));
+        game.start();
+
         Self {
             current_text: "Welcome!".to_string(),
-            player: Player {
-                tech: 3,
-                arts: 3,
-                bur: 3, //short for bureaucracy
-                und: 3, //short for underworld
-                checkmate_mod: 0,
-                rocketry_mod: 0,
-                pathology_mod: 0,
-                civic_engineering_mod: 0,
-                apparatchik_mod: 0,
-                quota_mod: 0,
-                robot_mod: 0,
-                dossier_mod: 0,
-                delusion_mod: 0,
-                lens_mod: 0,
-                opera_mod: 0,
-                transcendence_mod: 0,
-                gunsmoke_mod: 0,
-                prohibition_mod: 0,
-                gizmo_mod: 0,
-                oldtime_religion_mod: 0,
-                items: vec![],
-                xp: 0,
-                skill_points: 0,
-                dialogues_entered: HashSet::new(),
-                flags: HashSet::new(),
-            },
-            locations: create_locations(),
-            current_location_id: "Vestibule".to_string(), // Start in the Vestibule
-            current_dialogue_id: Some("Start".to_string()), // Start with the "Start" dialogue
+            project_path: project_path.to_string(),
+            game,
+            speakers: load_speakers(&speaker_path(project_path)),
+            images: ImageCache::new(Project::base_dir(project_path)),
+            markup,
+            log: vec![],
+            languages: languages(project_path),
+            dice: StdRng::from_entropy(),
             state: GameState::CharacterCreation,
             previous_dialogue_id: None,
-            current_time: Time {
-                day: 1,
-                hour: 3,
-                minute: 30,
-            },
             isometric_space: IsometricSpace {
                 ..Default::default()
             },
         }
     }
-}
-
-#[derive(Debug, PartialEq)]
-struct Time {
-    day: i32,
-    hour: i32,
-    minute: i32,
-}
-
-impl Time {
-    fn _increase(&mut self, added_minutes: i32) {
-        let days_increased: i32 = added_minutes / 1440;
-
-        let hours_increased: i32 = (added_minutes - 1440 * days_increased) / 60;
-
-        let minutes_increased: i32 =
-            (added_minutes - 1440 * days_increased) - (60 * hours_increased);
-
-        self.day += days_increased;
-        self.hour += hours_increased;
-        self.minute += minutes_increased;
-
-        if self.minute > 59 {
-            self.minute = self.minute % 60;
-            self.hour += 1;
-        }
-
-        if self.hour > 23 {
-            self.hour = 0;
-            self.day += 1;
-        };
-    }
-}
-
-fn _roll_dice() -> (i32, i32) {
-    let mut rng = rand::thread_rng();
-    (rng.gen_range(1..=6), rng.gen_range(1..=6))
-}
-
-// Challenge logic
-fn _handle_challenge(player: &Player, option: &DialogueOption) -> bool {
-    if let Some(challenge_attribute) = &option.challenge_attribute {
-        if let Some(challenge_number) = option.challenge_number {
-            let attribute_value = match challenge_attribute.as_str() {
-                "checkmate" => player.checkmate(),
-                "rocketry" => player.rocketry(),
-                "pathology" => player.pathology(),
-                "civic engineering" => player.civic_engineering(),
-                "apparatchik" => player.apparatchik(),
-                "quota" => player.quota(),
-                "robot" => player.robot(),
-                "dossier" => player.dossier(),
-                "delusion" => player.delusion(),
-                "lens" => player.lens(),
-                "opera" => player.opera(),
-                "transcendence" => player.transcendence(),
-                "gunsmoke" => player.gunsmoke(),
-                "prohibition" => player.prohibition(),
-                "gizmo" => player.gizmo(),
-                "oldtime religion" => player.oldtime_religion(),
-                _ => 0,
-            };
-
-            let (die1, die2) = _roll_dice();
-            let roll_sum = die1 + die2;
-
-            println!("You rolled: {} + {} = {}", die1, die2, roll_sum);
-
-            if die1 == 6 && die2 == 6 {
-                println!("Double sixes! Automatic success.");
-                return true;
-            } else if die1 == 1 && die2 == 1 {
-                println!("Double ones! Automatic failure.");
-                return false;
-            }
-
-            let total = roll_sum + attribute_value;
-            if total >= challenge_number {
-                println!(
-                    "Success! You needed {}, and you got {}.",
-                    challenge_number, total
-                );
-                return true;
-            } else {
-                println!(
-                    "Failure. You needed {}, but you got {}.",
-                    challenge_number, total
-                );
-                return false;
-            }
-        }
-    }
-    false
-}
-
-#[allow(dead_code)]
-struct Player {
-    tech: i32,
-    arts: i32,
-    bur: i32, //short for bureaucracy
-    und: i32, //short for underworld
-    checkmate_mod: i32,
-    rocketry_mod: i32,
-    pathology_mod: i32,
-    civic_engineering_mod: i32,
-    apparatchik_mod: i32,
-    quota_mod: i32,
-    robot_mod: i32,
-    dossier_mod: i32,
-    delusion_mod: i32,
-    lens_mod: i32,
-    opera_mod: i32,
-    transcendence_mod: i32,
-    gunsmoke_mod: i32,
-    prohibition_mod: i32,
-    gizmo_mod: i32,
-    oldtime_religion_mod: i32,
-    items: Vec<String>,
-    xp: i32,
-    skill_points: i32,
-    dialogues_entered: HashSet<String>,
-    flags: HashSet<String>,
-}
-
-#[allow(dead_code)]
-impl Player {
-    fn checkmate(&self) -> i32 {
-        self.tech + self.checkmate_mod
-    }
-
-    fn rocketry(&self) -> i32 {
-        self.tech + self.rocketry_mod
-    }
 
-    fn pathology(&self) -> i32 {
-        self.tech + self.pathology_mod
+    fn travel(&mut self, to: &str) -> Result<Turn, NavigationError> {
+        let turn = self.game.travel(to)?;
+        self.previous_dialogue_id = None;
+        Ok(turn)
     }
 
-    fn civic_engineering(&self) -> i32 {
-        self.tech + self.civic_engineering_mod
+    fn talk(&mut self, conversation: &str) -> Result<Turn, NavigationError> {
+        let turn = self.game.talk(conversation)?;
+        self.previous_dialogue_id = None;
+        Ok(turn)
     }
 
-    fn apparatchik(&self) -> i32 {
-        self.bur + self.apparatchik_mod
-    }
-
-    fn quota(&self) -> i32 {
-        self.bur + self.quota_mod
-    }
-
-    fn robot(&self) -> i32 {
-        self.bur + self.robot_mod
-    }
-
-    fn dossier(&self) -> i32 {
-        self.bur + self.dossier_mod
-    }
-
-    fn delusion(&self) -> i32 {
-        self.arts + self.delusion_mod
-    }
-
-    fn lens(&self) -> i32 {
-        self.arts + self.lens_mod
-    }
-
-    fn opera(&self) -> i32 {
-        self.arts + self.opera_mod
-    }
-
-    fn transcendence(&self) -> i32 {
-        self.arts + self.transcendence_mod
-    }
-
-    fn gunsmoke(&self) -> i32 {
-        self.und + self.gunsmoke_mod
+    // Switches the text to another language's table; None goes back to the source text
+    fn set_language(&mut self, name: Option<&str>) -> std::io::Result<()> {
+        self.game.language = match name {
+            Some(name) => Some(Language::load(&self.project_path, name)?),
+            None => None,
+        };
+        Ok(())
     }
 
-    fn prohibition(&self) -> i32 {
-        self.und + self.prohibition_mod
+    // Moves on from the current dialogue after the player picked an option, rolling for its challenge
+    fn choose_option(&mut self, option: &DialogueOption) -> Result<Turn, NavigationError> {
+        let previous = self.game.position.dialogue.clone();
+        let dice = &mut self.dice;
+        let turn = self.game.choose(option, || roll_dice(dice))?;
+        self.previous_dialogue_id = previous;
+        Ok(turn)
     }
 
-    fn gizmo(&self) -> i32 {
-        self.und + self.gizmo_mod
+    // What the player should hear about a move: the roll, then any XP and skill point it brought
+    fn announce(&mut self, turn: &Turn) {
+        if let Some(roll) = &turn.roll {
+            self.current_text = roll.to_string();
+        }
+        if turn.xp > 0 {
+            self.current_text = format!("You gained {} XP.", turn.xp);
+        }
+        if turn.skill_points > 0 {
+            self.current_text = format!("You gained a skill point! You now have {} skill points.", self.game.player.skill_points);
+        }
     }
+}
 
-    fn oldtime_religion(&self) -> i32 {
-        self.und + self.oldtime_religion_mod
-    }
+impl eframe::App for DialogueApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut switch_language = None;
+        egui::TopBottomPanel::top("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} - {}", self.game.time, self.game.position.location));
+                if !self.languages.is_empty() {
+                    let current = self.game.language.as_ref().map(|language| language.name.clone());
+                    egui::ComboBox::from_id_salt("language")
+                        .selected_text(current.as_deref().unwrap_or("Source"))
+                        .show_ui(ui, |ui| {
+                            if ui.selectable_label(current.is_none(), "Source").clicked() {
+                                switch_language = Some(None);
+                            }
+                            for name in &self.languages {
+                                if ui.selectable_label(current.as_ref() == Some(name), name).clicked() {
+                                    switch_language = Some(Some(name.clone()));
+                                }
+                            }
+                        });
+                }
+            });
+        });
+        if let Some(name) = switch_language {
+            if let Err(err) = self.set_language(name.as_deref()) {
+                self.current_text = format!("Couldn't load the translation: {}", err);
+            }
+        }
 
-    fn total_points(&self) -> i32 {
-        self.tech + self.arts + self.bur + self.und
-    }
+        let mut chosen = None;
+        let mut talk = None;
+        let mut travel = None;
+        let conversations: Vec<String> = self.game.conversations().into_iter().cloned().collect();
+        let exits: Vec<Exit> = self.game.exits().into_iter().cloned().collect();
+        let game = &self.game;
+        let variables = |name: &str| game.variable(name);
+        let has = |condition: &str| game.player.has(condition);
+        let context = MarkupContext {
+            speakers: &self.speakers,
+            variables: &variables,
+            has: &has,
+        };
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // Everything said so far, oldest first
+            egui::ScrollArea::vertical()
+                .id_salt("dialogue_log")
+                .max_height(ui.available_height() / 3.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for entry in &self.log {
+                        ui.horizontal_wrapped(|ui| {
+                            if let Some(name) = speaker_label(&self.speakers, &entry.speaker) {
+                                ui.label(name);
+                            }
+                            ui.label(layout_job(self.markup.get(&entry.text), &context, ui.style()));
+                        });
+                    }
+                });
+            ui.separator();
 
-    fn remaining_points(&self) -> i32 {
-        12 - self.total_points()
-    }
+            let location = game.position.current_location(&game.locations);
+            if let Some(dialogue) = game.dialogue() {
+                let background = location.and_then(|location| location.background.as_deref());
+                if let Some(index) = show_dialogue(ui, &mut self.images, &mut self.markup, &dialogue, background, &context, true) {
+                    chosen = Some(dialogue.options[index].clone());
+                }
+            } else if let Some(location) = location {
+                show_background(ui, &mut self.images, location.background.as_deref());
+                ui.heading(&location.name);
+                for conversation in &conversations {
+                    if ui.button(format!("Talk: {}", conversation)).clicked() {
+                        talk = Some(conversation.clone());
+                    }
+                }
+                for exit in &exits {
+                    let minutes = exit.time_cost.unwrap_or(0);
+                    if ui.button(format!("Go to {} ({} min)", exit.to, minutes)).clicked() {
+                        travel = Some(exit.to.clone());
+                    }
+                }
+            }
 
-    fn is_valid(&self) -> bool {
-        self.tech >= 1
-            && self.arts >= 1
-            && self.bur >= 1
-            && self.und >= 1
-            && self.tech <= 6
-            && self.arts <= 6
-            && self.bur <= 6
-            && self.und <= 6
-            && self.total_points() == 12
-    }
+            ui.separator();
+            ui.label(&self.current_text);
+        });
 
-    fn add_xp(&mut self, amount: i32) {
-        self.xp += amount;
-
-        // Handle leveling up
-        while self.xp >= 100 {
-            self.xp -= 100; // Reset XP and preserve the overflow
-            self.skill_points += 1; // Award skill points
-            println!(
-                "You gained a skill point! You now have {} skill points.",
-                self.skill_points
-            );
+        if let Some(option) = &chosen {
+            if let Some(dialogue) = self.game.dialogue() {
+                let said = LogEntry {
+                    speaker: dialogue.speaker.clone(),
+                    text: dialogue.intro.clone(),
+                };
+                self.log.push(said);
+            }
+            self.log.push(LogEntry {
+                speaker: String::new(),
+                text: format!("> {}", option.description),
+            });
         }
-    }
-}
 
-impl Default for Player {
-    fn default() -> Self {
-        Self {
-            tech: 1,
-            arts: 1,
-            bur: 1, //short for bureaucracy
-            und: 1, //short for underworld
-            checkmate_mod: 0,
-            rocketry_mod: 0,
-            pathology_mod: 0,
-            civic_engineering_mod: 0,
-            apparatchik_mod: 0,
-            quota_mod: 0,
-            robot_mod: 0,
-            dossier_mod: 0,
-            delusion_mod: 0,
-            lens_mod: 0,
-            opera_mod: 0,
-            transcendence_mod: 0,
-            gunsmoke_mod: 0,
-            prohibition_mod: 0,
-            gizmo_mod: 0,
-            oldtime_religion_mod: 0,
-            items: vec![],
-            xp: 0,
-            skill_points: 0,
-            dialogues_entered: HashSet::new(),
-            flags: HashSet::new(),
+        let result = if let Some(option) = chosen {
+            self.choose_option(&option)
+        } else if let Some(conversation) = talk {
+            self.talk(&conversation)
+        } else if let Some(to) = travel {
+            self.travel(&to)
+        } else {
+            Ok(Turn::default())
+        };
+        match result {
+            Ok(turn) => self.announce(&turn),
+            Err(err) => self.current_text = err.to_string(),
         }
     }
 }
 
-struct DialogueEditorApp {
-    dialogues: HashMap<String, Dialogue>, // Dialogues being edited
-    selected_dialogue: Option<String>,    // Currently selected dialogue ID
-    temp_id: String,                      // Temporary field for editing dialogue ID
+fn roll_dice(rng: &mut impl Rng) -> (i32, i32) {
+    (rng.gen_range(1..=6), rng.gen_range(1..=6))
 }
 
-impl Default for DialogueEditorApp {
-    fn default() -> Self {
-        //Load in dialogues from the dedicated file
-        //In the future, plan to transform this to load dialogues from room-specific files
-
-        let (dialogues, temp_id) = initialize_dialogues("src/dialogues/clock.json").unwrap();
+// adding clap functionality so I can specify which dialogue file to access from main
 
-        Self {
-            dialogues,
-            selected_dialogue: None,
-            temp_id,
-        }
-    }
+#[derive(Parser)]
+#[command(about = "Tools for writing and playing Shadow Soldiers dialogues")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-impl eframe::App for DialogueEditorApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Dialogue Editor");
+#[derive(Subcommand)]
+enum Command {
+    /// Open the dialogue editor (the default)
+    Edit {
+        /// Dialogue JSON file to open first
+        #[arg(default_value = DEFAULT_DIALOGUE_FILE)]
+        file: String,
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+    },
+    /// Play the game from the project's start location; in the terminal, "lang de" switches language
+    Play {
+        /// Play in the terminal instead of a window
+        #[arg(long)]
+        terminal: bool,
+        /// Language to play in, from the project's locales folder; the dialogue files' own text otherwise
+        #[arg(long)]
+        language: Option<String>,
+        /// Seed for the dice, so a game can be played again with the same rolls
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+    },
+    /// Check every conversation for broken links, unknown speakers, markup errors, unknown variables and reachable stubs
+    Check {
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+        /// Count unknown fields in dialogue files as errors instead of warnings
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Check the project, dialogue and speaker files against the JSON Schemas, then run the content checks
+    Validate {
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+        /// Count unknown fields in dialogue files as errors in the content checks too
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Rewrite the JSON Schemas in the schema folder from the code
+    Schema,
+    /// Word counts, branching and checks per conversation, for planning
+    Stats {
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Play a conversation every possible way and report its endings, stuck states and inescapable loops
+    Explore {
+        /// Conversation ID from the project
+        conversation: String,
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+        /// Build to play as, written tech,arts,bur,und; can be repeated. Defaults to balanced and the four specialists
+        #[arg(long = "build", value_parser = parse_build)]
+        builds: Vec<Player>,
+        /// Play as every valid 12-point build
+        #[arg(long, conflicts_with = "builds")]
+        all_builds: bool,
+        /// Most states to explore per build
+        #[arg(long, default_value_t = DEFAULT_STATE_LIMIT)]
+        limit: usize,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Play a conversation many times with random builds, choices and dice, and add up the results
+    Playtest {
+        /// Conversation ID from the project
+        conversation: String,
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+        /// Number of playthroughs
+        #[arg(long, default_value_t = DEFAULT_RUNS)]
+        runs: usize,
+        /// Seed for the builds, choices and dice; the same seed gives the same results
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Most choices in one run, in case it goes round a loop
+        #[arg(long, default_value_t = DEFAULT_MAX_STEPS)]
+        max_steps: usize,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+        /// Print CSV instead of text
+        #[arg(long, conflicts_with = "json")]
+        csv: bool,
+    },
+    /// Replay golden transcripts against the current content and show what changed
+    ///
+    /// A new one is recorded by writing its header and "> " inputs and running it with --update
+    Transcript {
+        /// Transcript files to replay
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// Rewrite the files with what the game shows now, e.g. to record a new transcript from a script
+        #[arg(long)]
+        update: bool,
+    },
+    /// Add every dialogue string missing from the languages' string tables, creating tables as needed
+    Extract {
+        /// Languages to extract for, such as de or fr
+        #[arg(required = true)]
+        languages: Vec<String>,
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+    },
+    /// Report untranslated, stale and obsolete strings in every language of the project
+    Translations {
+        /// Project file listing the locations and their conversation files
+        #[arg(long, default_value = DEFAULT_PROJECT_FILE)]
+        project: String,
+    },
+    /// Rewrite dialogue files so unset fields are left out instead of written as empty strings
+    Migrate {
+        /// Dialogue JSON files to clean in place
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
+}
 
-            // Add buttons for creating and saving dialogues
-            ui.horizontal(|ui| {
-                if ui.button("New Dialogue").clicked() {
-                    self.create_dialogue();
+fn main() -> Result<(), eframe::Error> {
+    let cli = Cli::parse();
+
+    let command = cli.command.unwrap_or(Command::Edit {
+        file: DEFAULT_DIALOGUE_FILE.to_string(),
+        project: DEFAULT_PROJECT_FILE.to_string(),
+    });
+
+    match command {
+        Command::Edit { file, project } => {
+            let options = eframe::NativeOptions::default();
+            eframe::run_native(
+                "Dialogue Editor",
+                options,
+                Box::new(move |_cc| Ok(Box::new(DialogueEditorApp::new(&project, &file)))),
+            )
+        }
+        Command::Play { terminal, language, seed, project } => {
+            let mut app = DialogueApp::new(&project);
+            if let Some(seed) = seed {
+                app.dice = StdRng::seed_from_u64(seed);
+            }
+            if let Err(err) = app.set_language(language.as_deref()) {
+                eprintln!("Couldn't load the translation: {}", err);
+                std::process::exit(1);
+            }
+            if terminal {
+                if let Err(err) = terminal::run(&mut app) {
+                    eprintln!("{}", err);
                 }
-                if ui.button("Save").clicked() {
-                    save_to_file(&self.dialogues, "src/dialogues/clock.json".to_string());
+                return Ok(());
+            }
+            let options = eframe::NativeOptions::default();
+            eframe::run_native("Shadow Soldiers", options, Box::new(|_cc| Ok(Box::new(app))))
+        }
+        Command::Check { project: project_path, strict } => {
+            if check_project(&project_path, strictness(strict)) > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Validate { project: project_path, strict } => {
+            let files = project_files(&project_path);
+            let mut violations = 0;
+            for (file, kind) in &files {
+                match validate_file(file, *kind) {
+                    Ok(found) => {
+                        for violation in &found {
+                            println!("{}: {}", file, violation);
+                        }
+                        violations += found.len();
+                    }
+                    Err(err) => {
+                        println!("{}: {}", file, err);
+                        violations += 1;
+                    }
                 }
-            });
+            }
+            // The content checks only make sense once the files have the right shape
+            if violations > 0 {
+                println!("{} schema violation(s) found in {} file(s)", violations, files.len());
+                std::process::exit(1);
+            }
+            println!("{} file(s) match the schemas", files.len());
+            if check_project(&project_path, strictness(strict)) > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Schema => {
+            if let Err(err) = write_schemas() {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            for (path, _) in schemas() {
+                println!("Wrote {}", path);
+            }
+            Ok(())
+        }
+        Command::Stats { project: project_path, json } => {
+            let project = load_project_or_exit(&project_path);
+            let stats = project_stats(&project, &project_path, &project.load_conversations(&project_path));
+            if json {
+                match serde_json::to_string_pretty(&stats) {
+                    Ok(json) => println!("{}", json),
+                    Err(err) => eprintln!("{}", err),
+                }
+            } else {
+                print!("{}", stats);
+            }
+            Ok(())
+        }
+        Command::Explore { conversation, project: project_path, builds, all_builds, limit, json } => {
+            let project = load_project_or_exit(&project_path);
+            let Some(dialogues) = project.load_game_conversations(&project_path).remove(&conversation) else {
+                eprintln!("No conversation with ID '{}' in {}", conversation, project_path);
+                std::process::exit(1);
+            };
+            let builds = match (all_builds, builds.is_empty()) {
+                (true, _) => valid_builds(),
+                (false, true) => [(3, 3, 3, 3), (6, 2, 2, 2), (2, 6, 2, 2), (2, 2, 6, 2), (2, 2, 2, 6)]
+                    .map(|(tech, arts, bur, und)| Player::with_build(tech, arts, bur, und))
+                    .to_vec(),
+                (false, false) => builds,
+            };
 
-            // Use ScrollArea to wrap the rest of the content
-            egui::ScrollArea::vertical()
-                .id_salt("dialogue_scroll_area") // Provide a unique identifier for the scroll area
-                .show(ui, |ui| {
-                    // Display list of dialogues
-                    self.display_dialogue_list(ui);
-
-                    // Edit the selected dialogue
-                    if let Some(selected_id) = &self.selected_dialogue {
-                        if let Some(dialogue) = self.dialogues.get_mut(selected_id) {
-                            if self.temp_id.is_empty() {
-                                self.temp_id = selected_id.clone();
-                            }
-                            edit_dialogue(ui, selected_id, dialogue, &mut self.temp_id);
+            let reports: Vec<BuildReport> = builds.iter().map(|build| explore(&conversation, &dialogues, build, limit)).collect();
+            if json {
+                match serde_json::to_string_pretty(&reports) {
+                    Ok(json) => println!("{}", json),
+                    Err(err) => eprintln!("{}", err),
+                }
+            } else {
+                for report in &reports {
+                    println!("{}", report);
+                }
+            }
+            if reports.iter().any(|report| !report.stuck.is_empty() || !report.trapped.is_empty()) {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Playtest { conversation, project: project_path, runs, seed, max_steps, json, csv } => {
+            let project = load_project_or_exit(&project_path);
+            let Some(dialogues) = project.load_game_conversations(&project_path).remove(&conversation) else {
+                eprintln!("No conversation with ID '{}' in {}", conversation, project_path);
+                std::process::exit(1);
+            };
+            let report = playtest(&conversation, &dialogues, runs, seed, max_steps);
+            if json {
+                match serde_json::to_string_pretty(&report) {
+                    Ok(json) => println!("{}", json),
+                    Err(err) => eprintln!("{}", err),
+                }
+            } else if csv {
+                print!("{}", report.to_csv());
+            } else {
+                print!("{}", report);
+            }
+            Ok(())
+        }
+        Command::Transcript { files, update } => {
+            let mut failed = false;
+            for file in files {
+                match replay(&file) {
+                    Ok((_, None)) => println!("{}: ok", file),
+                    Ok((actual, Some(_))) if update => match std::fs::write(&file, actual) {
+                        Ok(()) => println!("{}: updated", file),
+                        Err(err) => {
+                            eprintln!("{}: {}", file, err);
+                            failed = true;
                         }
+                    },
+                    Ok((_, Some(difference))) => {
+                        println!("{}: changed\n{}", file, difference);
+                        failed = true;
+                    }
+                    Err(err) => {
+                        eprintln!("{}: {}", file, err);
+                        failed = true;
                     }
-                });
-        });
-    }
-}
-
-impl DialogueEditorApp {
-    fn create_dialogue(&mut self) {
-        let id = create_dialogue(&mut self.dialogues); // Use the function from lib.rs
-        self.temp_id = id.clone();
-        self.selected_dialogue = Some(id);
-    }
-
-    fn _update_dialogues(&mut self, ui: &mut egui::Ui) {
-        if let Some(selected_id) = &self.selected_dialogue {
-            if let Some(dialogue) = self.dialogues.remove(selected_id) {
-                let mut dialogue = dialogue;
-                if self.temp_id.is_empty() {
-                    self.temp_id = selected_id.clone(); // Initialize temp_id
                 }
-
-                if let Some(new_id) =
-                    edit_dialogue(ui, selected_id, &mut dialogue, &mut self.temp_id)
-                {
-                    self.dialogues.insert(new_id.clone(), dialogue);
-                    self.selected_dialogue = Some(new_id);
-                    self.temp_id.clear();
-                } else {
-                    self.dialogues.insert(selected_id.clone(), dialogue);
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Extract { languages, project: project_path } => {
+            let project = load_project_or_exit(&project_path);
+            let sources = source_strings(&project, &project_path, &project.load_conversations(&project_path));
+            for language in languages {
+                let mut table = match load_table(&project_path, &language) {
+                    Ok(table) => table,
+                    Err(err) => {
+                        eprintln!("{}: {}", language, err);
+                        continue;
+                    }
+                };
+                let added = extract(&mut table, &sources);
+                match save_table(&table, &project_path, &language) {
+                    Ok(()) => println!("{}: added {} string(s), {} in total", language, added, table.len()),
+                    Err(err) => eprintln!("{}: {}", language, err),
                 }
             }
+            Ok(())
+        }
+        Command::Translations { project: project_path } => {
+            let project = load_project_or_exit(&project_path);
+            let sources = source_strings(&project, &project_path, &project.load_conversations(&project_path));
+            for language in languages(&project_path) {
+                let table = match load_table(&project_path, &language) {
+                    Ok(table) => table,
+                    Err(err) => {
+                        eprintln!("{}: {}", language, err);
+                        continue;
+                    }
+                };
+                let statuses = report(&table, &sources);
+                let count = |status| statuses.iter().filter(|(_, s)| *s == status).count();
+                println!(
+                    "{}: {} untranslated, {} stale, {} obsolete of {} string(s)",
+                    language,
+                    count(StringStatus::Untranslated),
+                    count(StringStatus::Stale),
+                    count(StringStatus::Obsolete),
+                    sources.len()
+                );
+                for (key, status) in &statuses {
+                    println!("  {:?}: {}", status, key);
+                }
+                for (key, err) in markup_errors(&table) {
+                    println!("  Markup: {}: {}", key, err);
+                }
+            }
+            Ok(())
+        }
+        Command::Migrate { files } => {
+            for file in files {
+                match migrate_file(&file) {
+                    Ok(0) => println!("{}: already clean", file),
+                    Ok(cleared) => println!("{}: cleared {} empty field(s)", file, cleared),
+                    Err(err) => eprintln!("{}: {}", file, err),
+                }
+            }
+            Ok(())
         }
     }
+}
 
-    fn display_dialogue_list(&mut self, ui: &mut egui::Ui) {
-        ui.label("Available Dialogues:");
-        let mut to_delete = None;
-
-        // Iterate through all dialogues
-        for (id, _dialogue) in &self.dialogues {
-            ui.horizontal(|ui| {
-                // Button to edit a dialogue
-                if ui.button(format!("Edit: {}", id)).clicked() {
-                    self.selected_dialogue = Some(id.clone());
-                }
+fn strictness(strict: bool) -> Strictness {
+    if strict {
+        Strictness::Strict
+    } else {
+        Strictness::Lenient
+    }
+}
 
-                // Button to delete a dialogue
-                if ui.button("Delete").clicked() {
-                    to_delete = Some(id.clone());
+// Runs every content check on the project and prints what it finds, returning the number of problems
+// Unknown fields and unreadable dialogues are reported per file first; only errors count as problems
+fn check_project(project_path: &str, strictness: Strictness) -> usize {
+    let project = load_project_or_exit(project_path);
+    let speakers = load_speakers(&speaker_path(project_path));
+
+    let mut conversations = HashMap::new();
+    let mut load_errors = 0;
+    for file in project.conversation_files(project_path) {
+        match load_dialogue_file(&file.path, strictness) {
+            Ok(loaded) => {
+                for issue in &loaded.issues {
+                    println!("{}: {}", file.path, issue);
                 }
-            });
+                load_errors += loaded.issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+                conversations.insert(file.conversation, loaded.dialogues);
+            }
+            Err(err) => {
+                println!("{}: {}", file.path, err);
+                load_errors += 1;
+                conversations.insert(file.conversation, Dialogues::new());
+            }
         }
+    }
 
-        // Delete the selected dialogue
-        if let Some(id) = to_delete {
-            self.dialogues.remove(&id);
-        }
+    let problems = project.validate(project_path);
+    let issues = validate_conversations(&project, project_path, &conversations, &speakers);
+    for problem in &problems {
+        println!("{}: {}", project_path, problem);
     }
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let (stubs, incomplete, complete) = completeness_counts(&conversations);
+    println!("{} stub(s), {} incomplete and {} complete dialogue(s)", stubs, incomplete, complete);
+    let found = load_errors + problems.len() + issues.len();
+    println!("{} problem(s) found", found);
+    found
 }
 
-// adding clap functionality so I can specify which dialogue file to access from main
-
-fn main() -> Result<(), eframe::Error> {
-    let options = eframe::NativeOptions::default();
-    eframe::run_native(
-        "Dialogue Editor",
-        options,
-        Box::new(|_cc| Ok(Box::new(DialogueEditorApp::default()))),
-    )
+fn load_project_or_exit(project_path: &str) -> Project {
+    match Project::load(project_path) {
+        Ok(project) => project,
+        Err(err) => {
+            eprintln!("{}: {}", project_path, err);
+            std::process::exit(1);
+        }
+    }
 }
 
 // if I wanted to be able to specify the dialogue file to load and modify, it would make the most sense to add a -- command after cargo run in which I could specify the name, likely not the full filepath. Then I'd have to preserve that and pass it into the update method somehow
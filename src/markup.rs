@@ -0,0 +1,637 @@
+// Inline markup for dialogue text, parsed into styled spans
+//
+//   *emphasis*   **bold**   \* a literal star (backslash escapes any character)
+//   {skill:Gunsmoke|text}   text in the color of that skill
+//   {speaker:Grandfather Clock}   the speaker's display name, in their color
+//   {player.name}   a variable, filled in when the text is shown (see is_known_variable)
+//   {if:met_clock|Hello again.|Hello.}   text that depends on the game state; the second branch is optional
+//
+// A variant's condition is a flag or item the player has, its negation (!met_clock), or a comparison
+// of a variable with a number (visits >= 3). Tags can be nested inside skill spans and variants.
+// Spans are rendered as an egui LayoutJob for the game and editor, or as ANSI for the terminal.
+// egui's built-in fonts have no bold face, so there bold text is only drawn in the strong text color
+
+use std::collections::HashMap;
+use std::fmt;
+
+use eframe::egui;
+use egui::text::{LayoutJob, TextFormat};
+
+use crate::speakers::{display_name, find_speaker, speaker_label, SpeakerKind, Speakers};
+use crate::{Dialogue, SKILLS};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpanContent {
+    Text(String),
+    Speaker(String),  // Speaker ID, shown as their display name
+    Variable(String), // Looked up when rendering, since it changes as the game goes on
+    Variant {
+        condition: Condition,
+        then: Markup,
+        otherwise: Markup,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Has(String), // A flag set or an item carried
+    Not(Box<Condition>),
+    Compare { variable: String, op: CompareOp, value: i32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub content: SpanContent,
+    pub emphasis: bool,
+    pub bold: bool,
+    pub skill: Option<String>, // Skill whose color the span takes, spelled as in SKILLS
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Markup {
+    pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkupError {
+    pub position: usize, // Byte offset into the text
+    pub message: String,
+}
+
+impl fmt::Display for MarkupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for MarkupError {}
+
+#[derive(Clone, Default, PartialEq)]
+struct Style {
+    emphasis: bool,
+    bold: bool,
+    skill: Option<String>,
+}
+
+// Variables the runtime fills in: the player's name, XP and skills (spaces written as underscores),
+// how often the current dialogue and location have been visited, and the clock
+pub fn is_known_variable(name: &str) -> bool {
+    match name {
+        "player.name" | "player.xp" | "visits" | "location.visits" | "time.day" | "time.hour" | "time.minute" => true,
+        _ => name
+            .strip_prefix("player.")
+            .is_some_and(|skill| SKILLS.contains(&skill.replace('_', " ").as_str())),
+    }
+}
+
+impl Markup {
+    // Text with no markup in it, for showing a string that failed to parse as written
+    pub fn plain(text: &str) -> Self {
+        Self {
+            spans: vec![Span {
+                content: SpanContent::Text(text.to_string()),
+                emphasis: false,
+                bold: false,
+                skill: None,
+            }],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, MarkupError> {
+        let mut markup = Markup::default();
+        let mut style = Style::default();
+        let mut opened = Opened::default();
+        parse_into(text, 0, &mut style, &mut markup, &mut opened)?;
+
+        if style.bold {
+            return Err(error(opened.bold, "'**' is never closed"));
+        }
+        if style.emphasis {
+            return Err(error(opened.emphasis, "'*' is never closed"));
+        }
+        Ok(markup)
+    }
+
+    // Every variable the text reads, in variant branches and conditions too
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.walk(&mut |span| match &span.content {
+            SpanContent::Variable(name) => names.push(name.as_str()),
+            SpanContent::Variant { condition, .. } => names.extend(condition.variable()),
+            _ => {}
+        });
+        names
+    }
+
+    pub fn speakers(&self) -> Vec<&str> {
+        let mut ids = vec![];
+        self.walk(&mut |span| {
+            if let SpanContent::Speaker(id) = &span.content {
+                ids.push(id.as_str());
+            }
+        });
+        ids
+    }
+
+    pub fn unknown_variables(&self) -> Vec<&str> {
+        self.variables().into_iter().filter(|name| !is_known_variable(name)).collect()
+    }
+
+    fn walk<'a>(&'a self, visit: &mut impl FnMut(&'a Span)) {
+        for span in &self.spans {
+            visit(span);
+            if let SpanContent::Variant { then, otherwise, .. } = &span.content {
+                then.walk(visit);
+                otherwise.walk(visit);
+            }
+        }
+    }
+
+    // The spans to show right now, with each variant replaced by the branch its condition picks
+    fn visible<'a>(&'a self, context: &MarkupContext, out: &mut Vec<&'a Span>) {
+        for span in &self.spans {
+            match &span.content {
+                SpanContent::Variant { condition, then, otherwise } => {
+                    let branch = if condition.evaluate(context) { then } else { otherwise };
+                    branch.visible(context, out);
+                }
+                _ => out.push(span),
+            }
+        }
+    }
+}
+
+impl Condition {
+    fn parse(text: &str, position: usize) -> Result<Self, MarkupError> {
+        let text = text.trim();
+        if let Some(rest) = text.strip_prefix('!') {
+            return Ok(Condition::Not(Box::new(Condition::parse(rest, position + 1)?)));
+        }
+
+        // Two-character operators first, so ">=" isn't read as ">"
+        let operators = [
+            (">=", CompareOp::GreaterOrEqual),
+            ("<=", CompareOp::LessOrEqual),
+            ("!=", CompareOp::NotEqual),
+            ("==", CompareOp::Equal),
+            (">", CompareOp::Greater),
+            ("<", CompareOp::Less),
+        ];
+        for (symbol, op) in operators {
+            if let Some((variable, value)) = text.split_once(symbol) {
+                let variable = variable.trim();
+                if variable.is_empty() {
+                    return Err(error(position, &format!("nothing to compare before '{}'", symbol)));
+                }
+                let Ok(value) = value.trim().parse() else {
+                    return Err(error(position, &format!("'{}' must be compared with a whole number", variable)));
+                };
+                return Ok(Condition::Compare {
+                    variable: variable.to_string(),
+                    op,
+                    value,
+                });
+            }
+        }
+
+        if text.is_empty() {
+            return Err(error(position, "a variant needs a condition"));
+        }
+        Ok(Condition::Has(text.to_string()))
+    }
+
+    fn variable(&self) -> Option<&str> {
+        match self {
+            Condition::Has(_) => None,
+            Condition::Not(inner) => inner.variable(),
+            Condition::Compare { variable, .. } => Some(variable),
+        }
+    }
+
+    pub fn evaluate(&self, context: &MarkupContext) -> bool {
+        match self {
+            Condition::Has(name) => (context.has)(name),
+            Condition::Not(inner) => !inner.evaluate(context),
+            Condition::Compare { variable, op, value } => {
+                // A variable that isn't set, or isn't a number, makes the comparison false
+                let Some(current) = (context.variables)(variable).and_then(|v| v.parse::<i32>().ok()) else {
+                    return false;
+                };
+                match op {
+                    CompareOp::Less => current < *value,
+                    CompareOp::LessOrEqual => current <= *value,
+                    CompareOp::Greater => current > *value,
+                    CompareOp::GreaterOrEqual => current >= *value,
+                    CompareOp::Equal => current == *value,
+                    CompareOp::NotEqual => current != *value,
+                }
+            }
+        }
+    }
+}
+
+// Where the emphasis and bold that are still open were opened, for the error if they never close
+#[derive(Default)]
+struct Opened {
+    emphasis: usize,
+    bold: usize,
+}
+
+fn error(position: usize, message: &str) -> MarkupError {
+    MarkupError {
+        position,
+        message: message.to_string(),
+    }
+}
+
+fn push_text(markup: &mut Markup, style: &Style, text: &mut String) {
+    if text.is_empty() {
+        return;
+    }
+    markup.spans.push(Span {
+        content: SpanContent::Text(std::mem::take(text)),
+        emphasis: style.emphasis,
+        bold: style.bold,
+        skill: style.skill.clone(),
+    });
+}
+
+// Byte index of the '}' that closes a tag whose contents start at the beginning of `text`
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Splits a tag's contents on the '|'s that aren't inside a nested tag, keeping each part's offset
+fn split_branches(text: &str, start: usize) -> Vec<(usize, &str)> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut part_start = 0;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '|' if depth == 0 => {
+                parts.push((start + part_start, &text[part_start..i]));
+                part_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push((start + part_start, &text[part_start..]));
+    parts
+}
+
+// Parses text that starts at byte offset `start` of the original string
+fn parse_into(text: &str, start: usize, style: &mut Style, markup: &mut Markup, opened: &mut Opened) -> Result<(), MarkupError> {
+    let mut current = String::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let position = start + i;
+        match c {
+            '\\' => match chars.next() {
+                Some((_, escaped)) => current.push(escaped),
+                None => return Err(error(position, "'\\' at the end of the text escapes nothing")),
+            },
+            '*' => {
+                push_text(markup, style, &mut current);
+                if chars.peek().is_some_and(|(_, next)| *next == '*') {
+                    chars.next();
+                    style.bold = !style.bold;
+                    opened.bold = position;
+                } else {
+                    style.emphasis = !style.emphasis;
+                    opened.emphasis = position;
+                }
+            }
+            '{' => {
+                push_text(markup, style, &mut current);
+                let rest = &text[i + 1..];
+                let Some(end) = closing_brace(rest) else {
+                    return Err(error(position, "'{' is never closed"));
+                };
+                let tag = &rest[..end];
+                parse_tag(tag, position + 1, style, markup, opened)?;
+                // Skip past the tag and its closing brace
+                while chars.next_if(|(j, _)| *j <= i + 1 + end).is_some() {}
+            }
+            '}' => return Err(error(position, "'}' without a matching '{'")),
+            _ => current.push(c),
+        }
+    }
+    push_text(markup, style, &mut current);
+    Ok(())
+}
+
+fn parse_tag(tag: &str, start: usize, style: &mut Style, markup: &mut Markup, opened: &mut Opened) -> Result<(), MarkupError> {
+    let tag_span = |content, style: &Style| Span {
+        content,
+        emphasis: style.emphasis,
+        bold: style.bold,
+        skill: style.skill.clone(),
+    };
+
+    let Some((kind, rest)) = tag.split_once(':') else {
+        if tag.trim().is_empty() {
+            return Err(error(start, "empty '{}'"));
+        }
+        markup.spans.push(tag_span(SpanContent::Variable(tag.trim().to_string()), style));
+        return Ok(());
+    };
+    let rest_start = start + kind.len() + 1;
+
+    match kind {
+        "skill" => {
+            let Some((skill, inner)) = rest.split_once('|') else {
+                return Err(error(start, "a skill span needs '|' between the skill and its text"));
+            };
+            let skill = skill.trim().to_lowercase();
+            if !SKILLS.contains(&skill.as_str()) {
+                return Err(error(start, &format!("unknown skill '{}'", skill)));
+            }
+            let outer = style.skill.replace(skill);
+            parse_into(inner, start + tag.len() - inner.len(), style, markup, opened)?;
+            style.skill = outer;
+            Ok(())
+        }
+        "speaker" if !rest.trim().is_empty() => {
+            markup.spans.push(tag_span(SpanContent::Speaker(rest.trim().to_string()), style));
+            Ok(())
+        }
+        "speaker" => Err(error(start, "a speaker reference needs a speaker ID")),
+        "if" => {
+            let parts = split_branches(rest, rest_start);
+            if parts.len() < 2 {
+                return Err(error(start, "a variant needs '|' between the condition and its text"));
+            }
+            if parts.len() > 3 {
+                return Err(error(parts[3].0, "a variant has at most two branches"));
+            }
+            let condition = Condition::parse(parts[0].1, parts[0].0)?;
+
+            // Each branch starts in the surrounding style and has to leave it the way it found it
+            let mut branches = vec![];
+            for &(offset, text) in &parts[1..] {
+                let mut branch_style = style.clone();
+                let mut branch = Markup::default();
+                parse_into(text, offset, &mut branch_style, &mut branch, opened)?;
+                if branch_style != *style {
+                    return Err(error(offset, "'*' or '**' opened in a variant has to close inside it"));
+                }
+                branches.push(branch);
+            }
+            let otherwise = if branches.len() == 2 { branches.pop().unwrap_or_default() } else { Markup::default() };
+            let then = branches.pop().unwrap_or_default();
+            markup.spans.push(tag_span(SpanContent::Variant { condition, then, otherwise }, style));
+            Ok(())
+        }
+        _ => Err(error(start, &format!("unknown tag '{}'", kind))),
+    }
+}
+
+// Parsed text by the string it came from, filled once when dialogues are loaded
+// Strings that fail to parse are shown as written; the validator is where their errors get reported
+#[derive(Default)]
+pub struct MarkupCache {
+    parsed: HashMap<String, Markup>,
+}
+
+impl MarkupCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_dialogue(&mut self, dialogue: &Dialogue) {
+        for text in dialogue_texts(dialogue) {
+            self.get(text);
+        }
+    }
+
+    pub fn get(&mut self, text: &str) -> &Markup {
+        self.parsed
+            .entry(text.to_string())
+            .or_insert_with(|| Markup::parse(text).unwrap_or_else(|_| Markup::plain(text)))
+    }
+}
+
+// Every piece of player-facing text in a dialogue, with a name for where it sits
+pub fn labelled_texts(dialogue: &Dialogue) -> Vec<(String, &str)> {
+    let mut texts = vec![("intro".to_string(), dialogue.intro.as_str())];
+    for (i, option) in dialogue.options.iter().enumerate() {
+        texts.push((format!("options[{}].description", i), option.description.as_str()));
+    }
+    for (i, check) in dialogue.passive_check.iter().enumerate() {
+        if let Some(text) = &check.success_text {
+            texts.push((format!("passive_check[{}].success_text", i), text.as_str()));
+        }
+        if let Some(text) = &check.failure_text {
+            texts.push((format!("passive_check[{}].failure_text", i), text.as_str()));
+        }
+    }
+    texts
+}
+
+pub fn dialogue_texts(dialogue: &Dialogue) -> impl Iterator<Item = &str> {
+    labelled_texts(dialogue).into_iter().map(|(_, text)| text)
+}
+
+// What rendering needs besides the spans: speaker names and colors, the current variable values,
+// and whether the player has a flag or item, for variants
+pub struct MarkupContext<'a> {
+    pub speakers: &'a Speakers,
+    pub variables: &'a dyn Fn(&str) -> Option<String>,
+    pub has: &'a dyn Fn(&str) -> bool,
+}
+
+impl MarkupContext<'_> {
+    // Skills are colored like the skill's speaker in the catalogue
+    fn skill_color(&self, skill: &str) -> Option<egui::Color32> {
+        self.speakers
+            .values()
+            .find(|speaker| speaker.kind == SpeakerKind::Skill && speaker.name.eq_ignore_ascii_case(skill))
+            .and_then(|speaker| speaker.text_color())
+    }
+
+    // The text a span shows and the color it has on top of its style
+    fn resolve(&self, span: &Span) -> (String, Option<egui::Color32>) {
+        let skill_color = span.skill.as_deref().and_then(|skill| self.skill_color(skill));
+        match &span.content {
+            SpanContent::Text(text) => (text.clone(), skill_color),
+            SpanContent::Speaker(id) => {
+                let color = find_speaker(self.speakers, id).and_then(|speaker| speaker.text_color());
+                (display_name(self.speakers, id).to_string(), color.or(skill_color))
+            }
+            SpanContent::Variable(name) => ((self.variables)(name).unwrap_or_else(|| format!("{{{}}}", name)), skill_color),
+            SpanContent::Variant { .. } => (String::new(), None), // Replaced by a branch before rendering
+        }
+    }
+}
+
+// The text as the player would read it right now, without any styling
+pub fn to_plain(markup: &Markup, context: &MarkupContext) -> String {
+    let mut spans = vec![];
+    markup.visible(context, &mut spans);
+    spans.into_iter().map(|span| context.resolve(span).0).collect()
+}
+
+pub fn layout_job(markup: &Markup, context: &MarkupContext, style: &egui::Style) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    append_markup(&mut job, markup, context, style);
+    job
+}
+
+// Adds the spans to the end of a job, after whatever plain text the caller put in front
+pub fn append_markup(job: &mut LayoutJob, markup: &Markup, context: &MarkupContext, style: &egui::Style) {
+    let font_id = egui::TextStyle::Body.resolve(style);
+    let mut spans = vec![];
+    markup.visible(context, &mut spans);
+    for span in spans {
+        let (text, color) = context.resolve(span);
+        let speaker = matches!(span.content, SpanContent::Speaker(_));
+        // No bold font to switch to, so bold stands out by color alone
+        let default_color = if span.bold || speaker {
+            style.visuals.strong_text_color()
+        } else {
+            style.visuals.text_color()
+        };
+        let format = TextFormat {
+            font_id: font_id.clone(),
+            color: color.unwrap_or(default_color),
+            italics: span.emphasis,
+            ..Default::default()
+        };
+        job.append(&text, 0.0, format);
+    }
+}
+
+// Escape codes for a terminal: italic, bold and 24-bit color
+pub fn to_ansi(markup: &Markup, context: &MarkupContext) -> String {
+    let mut out = String::new();
+    let mut spans = vec![];
+    markup.visible(context, &mut spans);
+    for span in spans {
+        let (text, color) = context.resolve(span);
+        let mut codes = vec![];
+        if span.emphasis {
+            codes.push("3".to_string());
+        }
+        if span.bold || matches!(span.content, SpanContent::Speaker(_)) {
+            codes.push("1".to_string());
+        }
+        if let Some(color) = color {
+            codes.push(format!("38;2;{};{};{}", color.r(), color.g(), color.b()));
+        }
+        if codes.is_empty() {
+            out.push_str(&text);
+        } else {
+            out.push_str(&format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text));
+        }
+    }
+    out
+}
+
+// A speaker's name for the terminal, bold and in their color; narration has no name
+pub fn speaker_to_ansi(id: &str, context: &MarkupContext) -> Option<String> {
+    speaker_label(context.speakers, id)?;
+    let name = Span {
+        content: SpanContent::Speaker(id.to_string()),
+        emphasis: false,
+        bold: true,
+        skill: None,
+    };
+    Some(to_ansi(&Markup { spans: vec![name] }, context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, emphasis: bool, bold: bool, skill: Option<&str>) -> Span {
+        Span {
+            content: SpanContent::Text(text.to_string()),
+            emphasis,
+            bold,
+            skill: skill.map(str::to_string),
+        }
+    }
+
+    fn plain(markup: &Markup, has: bool) -> String {
+        let speakers = Speakers::new();
+        let has = move |_: &str| has;
+        let context = MarkupContext {
+            speakers: &speakers,
+            variables: &|name| (name == "visits").then(|| "3".to_string()),
+            has: &has,
+        };
+        to_plain(markup, &context)
+    }
+
+    #[test]
+    fn styles_nest() {
+        let markup = Markup::parse("*a **b {skill:Rocketry|c *d*}** e*").unwrap();
+        assert_eq!(
+            markup.spans,
+            vec![
+                text("a ", true, false, None),
+                text("b ", true, true, None),
+                text("c ", true, true, Some("rocketry")),
+                text("d", false, true, Some("rocketry")),
+                text(" e", true, false, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn variants_nest_and_pick_a_branch() {
+        let markup = Markup::parse("{if:met|Again, {if:visits >= 3|{visits} times|twice}.|Hello.}").unwrap();
+        assert_eq!(markup.variables(), vec!["visits", "visits"]);
+        assert_eq!(plain(&markup, true), "Again, 3 times.");
+        assert_eq!(plain(&markup, false), "Hello.");
+    }
+
+    #[test]
+    fn escapes_are_literal() {
+        let markup = Markup::parse(r"\*not emphasis\* \{nor a tag\} \\").unwrap();
+        assert_eq!(markup.spans, vec![text(r"*not emphasis* {nor a tag} \", false, false, None)]);
+        let markup = Markup::parse(r"{if:flag|a \| b}").unwrap();
+        assert_eq!(plain(&markup, true), "a | b");
+    }
+
+    #[test]
+    fn unclosed_tags_are_errors() {
+        let message = |text: &str| Markup::parse(text).unwrap_err().to_string();
+        assert_eq!(message("Hi *there"), "'*' is never closed at position 3");
+        assert_eq!(message("**Hi"), "'**' is never closed at position 0");
+        assert_eq!(message("Hi {player.name"), "'{' is never closed at position 3");
+        assert_eq!(message("Hi }"), "'}' without a matching '{' at position 3");
+        assert_eq!(message("{if:met|*hi}"), "'*' or '**' opened in a variant has to close inside it at position 8");
+        assert_eq!(message("trailing \\"), "'\\' at the end of the text escapes nothing at position 9");
+    }
+}
@@ -0,0 +1,331 @@
+// Where the player is in the game world, and the moves between locations, conversations and dialogues
+// Exit conditions are checked against whatever the caller says the player has, so this doesn't depend on Player
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::analysis::entry_points;
+use crate::project::split_link;
+use crate::{Dialogue, DialogueOption, Exit, Location};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NavigationError {
+    UnknownLocation(String),
+    NoExit { from: String, to: String },
+    Locked { to: String, condition: String },
+    UnknownConversation(String),
+    UnknownDialogue(String),
+    NotInConversation,
+    NotAtLocation { conversation: String, location: String }, // A move_to whose local link can't carry on there
+}
+
+impl fmt::Display for NavigationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NavigationError::UnknownLocation(name) => write!(f, "No location called '{}'", name),
+            NavigationError::NoExit { from, to } => write!(f, "There's no way from {} to {}", from, to),
+            NavigationError::Locked { to, condition } => write!(f, "The way to {} needs '{}'", to, condition),
+            NavigationError::UnknownConversation(id) => write!(f, "No conversation with ID '{}'", id),
+            NavigationError::UnknownDialogue(id) => write!(f, "No dialogue with ID '{}'", id),
+            NavigationError::NotInConversation => write!(f, "Not in a conversation"),
+            NavigationError::NotAtLocation { conversation, location } => {
+                write!(f, "{} can't carry on in {}, it isn't there", conversation, location)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NavigationError {}
+
+impl Exit {
+    // An exit with no condition is always open
+    pub fn is_open(&self, has: impl Fn(&str) -> bool) -> bool {
+        self.condition.as_deref().is_none_or(has)
+    }
+}
+
+// Where picking an option takes the player, seen from the conversation it's in
+// The game moves by these rules, and the explorer, playtests and transcripts follow the same ones
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome<'a> {
+    Dialogue(&'a str), // Another dialogue of the same conversation, after any move_to
+    Link(&'a str),     // "Conv/Id" in another conversation
+    Leave,             // No link and no move_to: the conversation ends
+    MoveTo(&'a str),   // No link: the player is left standing in the location
+}
+
+impl DialogueOption {
+    // Options with a visible_when condition only show up once the player has it
+    pub fn is_visible(&self, has: impl Fn(&str) -> bool) -> bool {
+        self.visible_when.as_deref().is_none_or(has)
+    }
+
+    // A bare link always names a dialogue of the option's own conversation, as does one qualified with it,
+    // so moving somewhere and linking to "Id" carries on the same conversation, which has to be there too
+    pub fn outcome(&self, success: bool, conversation: &str) -> Outcome<'_> {
+        let link = if success { &self.success_dialogue } else { &self.failure_dialogue };
+        match link.as_deref().map(|link| (link, split_link(link))) {
+            Some((_, (None, id))) => Outcome::Dialogue(id),
+            Some((_, (Some(other), id))) if other == conversation => Outcome::Dialogue(id),
+            Some((link, _)) => Outcome::Link(link),
+            None => self.move_to.as_deref().map_or(Outcome::Leave, Outcome::MoveTo),
+        }
+    }
+}
+
+impl Dialogue {
+    pub fn visible_options(&self, has: impl Fn(&str) -> bool) -> Vec<&DialogueOption> {
+        self.options.iter().filter(|option| option.is_visible(&has)).collect()
+    }
+}
+
+impl Location {
+    // The exits the player can take right now, in the order the project lists them
+    pub fn available_exits(&self, has: impl Fn(&str) -> bool) -> Vec<&Exit> {
+        self.exits.iter().filter(|exit| exit.is_open(&has)).collect()
+    }
+
+    // Conversation IDs sorted, since the map doesn't keep the project's order
+    pub fn conversation_ids(&self) -> Vec<&String> {
+        let mut ids: Vec<&String> = self.conversations.keys().collect();
+        ids.sort();
+        ids
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub location: String,
+    pub conversation: Option<String>,
+    pub dialogue: Option<String>, // Only set while in a conversation
+}
+
+impl Position {
+    // Standing in a location, not talking to anyone
+    pub fn at(location: &str) -> Self {
+        Self {
+            location: location.to_string(),
+            conversation: None,
+            dialogue: None,
+        }
+    }
+
+    pub fn current_location<'a>(&self, locations: &'a HashMap<String, Location>) -> Option<&'a Location> {
+        locations.get(&self.location)
+    }
+
+    fn has_conversation(&self, locations: &HashMap<String, Location>, conversation: &str) -> bool {
+        self.current_location(locations)
+            .is_some_and(|location| location.conversations.contains_key(conversation))
+    }
+
+    pub fn current_dialogue<'a>(&self, locations: &'a HashMap<String, Location>) -> Option<&'a Dialogue> {
+        let conversation = self.current_location(locations)?.conversations.get(self.conversation.as_ref()?)?;
+        conversation.dialogues.get(self.dialogue.as_ref()?)
+    }
+
+    // Walks through an exit of the current location; returns the minutes it took
+    pub fn travel(
+        &mut self,
+        locations: &HashMap<String, Location>,
+        to: &str,
+        has: impl Fn(&str) -> bool,
+    ) -> Result<i32, NavigationError> {
+        let here = self
+            .current_location(locations)
+            .ok_or_else(|| NavigationError::UnknownLocation(self.location.clone()))?;
+        let exit = here.exits.iter().find(|exit| exit.to == to).ok_or_else(|| NavigationError::NoExit {
+            from: self.location.clone(),
+            to: to.to_string(),
+        })?;
+        if !exit.is_open(has) {
+            return Err(NavigationError::Locked {
+                to: to.to_string(),
+                condition: exit.condition.clone().unwrap_or_default(),
+            });
+        }
+        if !locations.contains_key(to) {
+            return Err(NavigationError::UnknownLocation(to.to_string()));
+        }
+        *self = Position::at(to);
+        Ok(exit.time_cost.unwrap_or(0))
+    }
+
+    // Starts a conversation of the current location at its entry point
+    pub fn enter_conversation(&mut self, locations: &HashMap<String, Location>, id: &str) -> Result<(), NavigationError> {
+        let conversation = self
+            .current_location(locations)
+            .and_then(|location| location.conversations.get(id))
+            .ok_or_else(|| NavigationError::UnknownConversation(id.to_string()))?;
+        let entry = entry_points(&conversation.dialogues)
+            .into_iter()
+            .next()
+            .ok_or_else(|| NavigationError::UnknownDialogue(id.to_string()))?;
+        self.conversation = Some(id.to_string());
+        self.dialogue = Some(entry);
+        Ok(())
+    }
+
+    pub fn leave_conversation(&mut self) {
+        self.conversation = None;
+        self.dialogue = None;
+    }
+
+    // Follows an option link: "Id" stays in the current conversation, "Conv/Id" jumps to another
+    // conversation, moving to its location if it belongs somewhere else
+    pub fn follow_link(&mut self, locations: &HashMap<String, Location>, link: &str) -> Result<(), NavigationError> {
+        let (conversation, dialogue) = match split_link(link) {
+            (Some(conversation), dialogue) => (conversation.to_string(), dialogue),
+            (None, dialogue) => (self.conversation.clone().ok_or(NavigationError::NotInConversation)?, dialogue),
+        };
+
+        // Prefer the current location, then look through the rest of the world
+        let location = if self.has_conversation(locations, &conversation) {
+            self.location.clone()
+        } else {
+            locations
+                .iter()
+                .find(|(_, location)| location.conversations.contains_key(&conversation))
+                .map(|(name, _)| name.clone())
+                .ok_or_else(|| NavigationError::UnknownConversation(conversation.clone()))?
+        };
+
+        if !locations[&location].conversations[&conversation].dialogues.contains_key(dialogue) {
+            return Err(NavigationError::UnknownDialogue(link.to_string()));
+        }
+        *self = Position {
+            location,
+            conversation: Some(conversation),
+            dialogue: Some(dialogue.to_string()),
+        };
+        Ok(())
+    }
+
+    // Applies a chosen option: moves location if it says so, then goes where the option's outcome says
+    // A move with a local link only works if the new location has the conversation too, rather than the
+    // link quietly taking the player back to where they came from
+    pub fn choose_option(
+        &mut self,
+        locations: &HashMap<String, Location>,
+        option: &DialogueOption,
+        success: bool,
+    ) -> Result<i32, NavigationError> {
+        // Work on a copy, so an option that goes nowhere leaves the player where they were
+        let mut next = self.clone();
+        let mut minutes = 0;
+        if let Some(to) = &option.move_to {
+            if !locations.contains_key(to) {
+                return Err(NavigationError::UnknownLocation(to.clone()));
+            }
+            // A move along an existing exit takes as long as walking it
+            minutes = self
+                .current_location(locations)
+                .and_then(|here| here.exits.iter().find(|exit| exit.to == *to))
+                .and_then(|exit| exit.time_cost)
+                .unwrap_or(0);
+            next = Position::at(to);
+        }
+
+        let conversation = self.conversation.as_deref().unwrap_or_default();
+        match option.outcome(success, conversation) {
+            Outcome::Dialogue(_) if self.conversation.is_none() => return Err(NavigationError::NotInConversation),
+            Outcome::Dialogue(_) if option.move_to.is_some() && !next.has_conversation(locations, conversation) => {
+                return Err(NavigationError::NotAtLocation {
+                    conversation: conversation.to_string(),
+                    location: next.location,
+                });
+            }
+            Outcome::Dialogue(id) => next.follow_link(locations, &format!("{}/{}", conversation, id))?,
+            Outcome::Link(link) => next.follow_link(locations, link)?,
+            Outcome::Leave => next.leave_conversation(),
+            Outcome::MoveTo(_) => {}
+        }
+        *self = next;
+        Ok(minutes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Conversation;
+
+    fn option(link: Option<&str>, move_to: Option<&str>) -> DialogueOption {
+        DialogueOption {
+            success_dialogue: link.map(str::to_string),
+            move_to: move_to.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    // A hall with a clock to talk to, and an empty street next to it
+    fn world() -> HashMap<String, Location> {
+        let mut clock = Conversation::new("Clock".to_string());
+        clock.add_dialogue("Start".to_string(), Dialogue::default());
+        clock.add_dialogue("Later".to_string(), Dialogue::default());
+        let mut hall = Location::new("Hall".to_string());
+        hall.add_conversation("Clock".to_string(), clock);
+        [("Hall".to_string(), hall), ("Street".to_string(), Location::new("Street".to_string()))].into_iter().collect()
+    }
+
+    fn talking(dialogue: &str) -> Position {
+        Position {
+            location: "Hall".to_string(),
+            conversation: Some("Clock".to_string()),
+            dialogue: Some(dialogue.to_string()),
+        }
+    }
+
+    #[test]
+    fn moving_with_a_local_link_needs_the_conversation_there() {
+        let locations = world();
+        let mut position = talking("Start");
+        let err = position.choose_option(&locations, &option(Some("Later"), Some("Street")), true);
+        assert_eq!(
+            err,
+            Err(NavigationError::NotAtLocation {
+                conversation: "Clock".to_string(),
+                location: "Street".to_string()
+            })
+        );
+        assert_eq!(position, talking("Start"));
+
+        // Moving to where the conversation already is carries it on
+        position.choose_option(&locations, &option(Some("Later"), Some("Hall")), true).unwrap();
+        assert_eq!(position, talking("Later"));
+
+        position.choose_option(&locations, &option(None, Some("Street")), true).unwrap();
+        assert_eq!(position, Position::at("Street"));
+    }
+
+    #[test]
+    fn outcomes_read_links_from_the_options_conversation() {
+        let options = [
+            option(Some("Later"), Some("Street")),
+            option(Some("Clock/Later"), None),
+            option(Some("Intro/Start"), None),
+            option(None, Some("Street")),
+            option(None, None),
+        ];
+        let outcomes: Vec<Outcome> = options.iter().map(|option| option.outcome(true, "Clock")).collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                Outcome::Dialogue("Later"),
+                Outcome::Dialogue("Later"),
+                Outcome::Link("Intro/Start"),
+                Outcome::MoveTo("Street"),
+                Outcome::Leave,
+            ]
+        );
+    }
+
+    #[test]
+    fn a_failed_option_leaves_the_position_alone() {
+        let locations = world();
+        let mut position = talking("Start");
+        let err = position.choose_option(&locations, &option(Some("Missing"), Some("Hall")), true);
+        assert_eq!(err, Err(NavigationError::UnknownDialogue("Clock/Missing".to_string())));
+        assert_eq!(position, talking("Start"));
+    }
+}
@@ -0,0 +1,278 @@
+// The player character: attributes, skills derived from them, and what they carry and have done
+// Shared by the game, the terminal player and the simulations, so they all play by the same rules
+
+use std::collections::HashSet;
+
+use crate::{Dialogue, DialogueOption};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub name: String,
+    pub tech: i32,
+    pub arts: i32,
+    pub bur: i32, //short for bureaucracy
+    pub und: i32, //short for underworld
+    pub checkmate_mod: i32,
+    pub rocketry_mod: i32,
+    pub pathology_mod: i32,
+    pub civic_engineering_mod: i32,
+    pub apparatchik_mod: i32,
+    pub quota_mod: i32,
+    pub robot_mod: i32,
+    pub dossier_mod: i32,
+    pub delusion_mod: i32,
+    pub lens_mod: i32,
+    pub opera_mod: i32,
+    pub transcendence_mod: i32,
+    pub gunsmoke_mod: i32,
+    pub prohibition_mod: i32,
+    pub gizmo_mod: i32,
+    pub oldtime_religion_mod: i32,
+    pub items: Vec<String>,
+    pub xp: i32,
+    pub skill_points: i32,
+    pub dialogues_entered: HashSet<String>,
+    pub flags: HashSet<String>,
+}
+
+impl Player {
+    pub fn checkmate(&self) -> i32 {
+        self.tech + self.checkmate_mod
+    }
+
+    pub fn rocketry(&self) -> i32 {
+        self.tech + self.rocketry_mod
+    }
+
+    pub fn pathology(&self) -> i32 {
+        self.tech + self.pathology_mod
+    }
+
+    pub fn civic_engineering(&self) -> i32 {
+        self.tech + self.civic_engineering_mod
+    }
+
+    pub fn apparatchik(&self) -> i32 {
+        self.bur + self.apparatchik_mod
+    }
+
+    pub fn quota(&self) -> i32 {
+        self.bur + self.quota_mod
+    }
+
+    pub fn robot(&self) -> i32 {
+        self.bur + self.robot_mod
+    }
+
+    pub fn dossier(&self) -> i32 {
+        self.bur + self.dossier_mod
+    }
+
+    pub fn delusion(&self) -> i32 {
+        self.arts + self.delusion_mod
+    }
+
+    pub fn lens(&self) -> i32 {
+        self.arts + self.lens_mod
+    }
+
+    pub fn opera(&self) -> i32 {
+        self.arts + self.opera_mod
+    }
+
+    pub fn transcendence(&self) -> i32 {
+        self.arts + self.transcendence_mod
+    }
+
+    pub fn gunsmoke(&self) -> i32 {
+        self.und + self.gunsmoke_mod
+    }
+
+    pub fn prohibition(&self) -> i32 {
+        self.und + self.prohibition_mod
+    }
+
+    pub fn gizmo(&self) -> i32 {
+        self.und + self.gizmo_mod
+    }
+
+    pub fn oldtime_religion(&self) -> i32 {
+        self.und + self.oldtime_religion_mod
+    }
+
+    // A skill's value by the name dialogue files use for it
+    pub fn skill(&self, name: &str) -> Option<i32> {
+        Some(match name {
+            "checkmate" => self.checkmate(),
+            "rocketry" => self.rocketry(),
+            "pathology" => self.pathology(),
+            "civic engineering" => self.civic_engineering(),
+            "apparatchik" => self.apparatchik(),
+            "quota" => self.quota(),
+            "robot" => self.robot(),
+            "dossier" => self.dossier(),
+            "delusion" => self.delusion(),
+            "lens" => self.lens(),
+            "opera" => self.opera(),
+            "transcendence" => self.transcendence(),
+            "gunsmoke" => self.gunsmoke(),
+            "prohibition" => self.prohibition(),
+            "gizmo" => self.gizmo(),
+            "oldtime religion" => self.oldtime_religion(),
+            _ => return None,
+        })
+    }
+
+    // Values for {player.*} in dialogue text; skills are written with underscores, as in {player.civic_engineering}
+    pub fn variable(&self, name: &str) -> Option<String> {
+        match name.strip_prefix("player.")? {
+            "name" => Some(self.name.clone()),
+            "xp" => Some(self.xp.to_string()),
+            skill => self.skill(&skill.replace('_', " ")).map(|value| value.to_string()),
+        }
+    }
+
+    pub fn total_points(&self) -> i32 {
+        self.tech + self.arts + self.bur + self.und
+    }
+
+    pub fn remaining_points(&self) -> i32 {
+        12 - self.total_points()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.tech >= 1
+            && self.arts >= 1
+            && self.bur >= 1
+            && self.und >= 1
+            && self.tech <= 6
+            && self.arts <= 6
+            && self.bur <= 6
+            && self.und <= 6
+            && self.total_points() == 12
+    }
+
+    // Exit conditions and the like name a flag the player has set or an item they carry
+    pub fn has(&self, condition: &str) -> bool {
+        self.flags.contains(condition) || self.items.iter().any(|item| item == condition)
+    }
+
+    // Returns how many skill points the XP was worth, for the caller to announce
+    pub fn add_xp(&mut self, amount: i32) -> i32 {
+        self.xp += amount;
+
+        // Handle leveling up
+        let mut gained = 0;
+        while self.xp >= 100 {
+            self.xp -= 100; // Reset XP and preserve the overflow
+            self.skill_points += 1; // Award skill points
+            gained += 1;
+        }
+        gained
+    }
+
+    // A fresh character with the four attributes set and no skill modifiers
+    pub fn with_build(tech: i32, arts: i32, bur: i32, und: i32) -> Self {
+        Self {
+            tech,
+            arts,
+            bur,
+            und,
+            ..Default::default()
+        }
+    }
+
+    // The effects of picking an option: its flags are set and its item picked up
+    pub fn choose(&mut self, option: &DialogueOption) {
+        self.flags.extend(option.flags.iter().flatten().cloned());
+        if let Some(item) = &option.item_to_pickup {
+            if !self.items.contains(item) {
+                self.items.push(item.clone());
+            }
+        }
+    }
+
+    // Reaching a dialogue, keyed "Conv/Id"; the XP reward is only given the first time
+    // Returns the XP gained
+    pub fn enter(&mut self, key: &str, dialogue: &Dialogue) -> i32 {
+        if !self.dialogues_entered.insert(key.to_string()) {
+            return 0;
+        }
+        let xp = dialogue.xp_reward.unwrap_or(0);
+        self.add_xp(xp);
+        xp
+    }
+
+    // The skill an option challenges and the number to reach, if it has a challenge
+    pub fn challenge<'a>(&self, option: &'a DialogueOption) -> Option<(&'a str, i32, i32)> {
+        let skill = option.challenge_attribute.as_deref()?;
+        Some((skill, self.skill(&skill.to_lowercase()).unwrap_or(0), option.challenge_number?))
+    }
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            name: "Comrade".to_string(),
+            tech: 1,
+            arts: 1,
+            bur: 1, //short for bureaucracy
+            und: 1, //short for underworld
+            checkmate_mod: 0,
+            rocketry_mod: 0,
+            pathology_mod: 0,
+            civic_engineering_mod: 0,
+            apparatchik_mod: 0,
+            quota_mod: 0,
+            robot_mod: 0,
+            dossier_mod: 0,
+            delusion_mod: 0,
+            lens_mod: 0,
+            opera_mod: 0,
+            transcendence_mod: 0,
+            gunsmoke_mod: 0,
+            prohibition_mod: 0,
+            gizmo_mod: 0,
+            oldtime_religion_mod: 0,
+            items: vec![],
+            xp: 0,
+            skill_points: 0,
+            dialogues_entered: HashSet::new(),
+            flags: HashSet::new(),
+        }
+    }
+}
+
+// "3,3,3,3" as tech, arts, bur and und
+pub fn parse_build(text: &str) -> Result<Player, String> {
+    let values: Vec<i32> = text
+        .split(',')
+        .map(|value| value.trim().parse::<i32>().map_err(|err| format!("'{}': {}", value, err)))
+        .collect::<Result<_, _>>()?;
+    let [tech, arts, bur, und] = values[..] else {
+        return Err("a build is four numbers: tech,arts,bur,und".to_string());
+    };
+    let player = Player::with_build(tech, arts, bur, und);
+    if !player.is_valid() {
+        return Err("each attribute must be 1 to 6, adding up to 12".to_string());
+    }
+    Ok(player)
+}
+
+// Two dice plus the skill against the target; double six always succeeds and double one always fails
+pub fn challenge_succeeds(skill: i32, target: i32, dice: (i32, i32)) -> bool {
+    match dice {
+        (6, 6) => true,
+        (1, 1) => false,
+        (die1, die2) => die1 + die2 + skill >= target,
+    }
+}
+
+// The chance of succeeding, over all 36 rolls
+pub fn success_chance(skill: i32, target: i32) -> f64 {
+    let successes = (1..=6)
+        .flat_map(|die1| (1..=6).map(move |die2| (die1, die2)))
+        .filter(|&dice| challenge_succeeds(skill, target, dice))
+        .count();
+    successes as f64 / 36.0
+}
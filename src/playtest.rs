@@ -0,0 +1,346 @@
+// Random playthroughs of a conversation, for balancing: random valid builds pick random visible options
+// and roll real dice, and the runs are added up into how often each dialogue is seen, how often each
+// challenge passes, which skills get tested and how much XP a run earns
+// Runs are seeded, so the same seed gives the same numbers
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use indexmap::IndexMap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+use crate::analysis::entry_points;
+use crate::navigation::Outcome;
+use crate::player::{challenge_succeeds, Player};
+use crate::Dialogues;
+
+pub const DEFAULT_RUNS: usize = 1000;
+pub const DEFAULT_MAX_STEPS: usize = 200; // Choices per run before giving up on it, in case it loops forever
+
+// Any valid 12-point build, every one equally likely
+pub fn random_build(rng: &mut impl Rng) -> Player {
+    loop {
+        let player = Player::with_build(rng.gen_range(1..=6), rng.gen_range(1..=6), rng.gen_range(1..=6), rng.gen_range(1..=6));
+        if player.is_valid() {
+            return player;
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct ChallengeStats {
+    pub dialogue: String,
+    pub option: usize, // Index into the dialogue's options
+    pub skill: String,
+    pub target: i32,
+    pub attempts: usize,
+    pub successes: usize,
+    pub success_rate: f64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct SkillStats {
+    pub attempts: usize,
+    pub successes: usize,
+    pub success_rate: f64,
+    pub rate_by_value: BTreeMap<i32, f64>, // Success rate by the player's skill value when tested
+    // Success rate at the highest skill value tested minus at the lowest: how much investing in it pays off
+    pub swing: f64,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct PlaytestReport {
+    pub seed: u64,
+    pub runs: usize,
+    pub average_steps: f64,
+    pub average_xp: f64,
+    pub reached: IndexMap<String, usize>, // Runs that saw each dialogue, in file order
+    pub challenges: Vec<ChallengeStats>,
+    pub skills: BTreeMap<String, SkillStats>,
+    pub endings: BTreeMap<String, usize>, // How runs ended: left, linked elsewhere, stuck or out of steps
+}
+
+fn rate(successes: usize, attempts: usize) -> f64 {
+    if attempts == 0 {
+        0.0
+    } else {
+        successes as f64 / attempts as f64
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    attempts: usize,
+    successes: usize,
+}
+
+impl Tally {
+    fn add(&mut self, success: bool) {
+        self.attempts += 1;
+        self.successes += usize::from(success);
+    }
+}
+
+pub fn playtest(conversation: &str, dialogues: &Dialogues, runs: usize, seed: u64, max_steps: usize) -> PlaytestReport {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let entries = entry_points(dialogues);
+
+    let mut reached: IndexMap<String, usize> = dialogues.keys().map(|id| (id.clone(), 0)).collect();
+    let mut challenges: IndexMap<(String, usize), (String, i32, Tally)> = IndexMap::new();
+    let mut skills: BTreeMap<String, BTreeMap<i32, Tally>> = BTreeMap::new();
+    let mut endings: BTreeMap<String, usize> = BTreeMap::new();
+    let mut total_steps = 0;
+    let mut total_xp = 0;
+
+    for _ in 0..runs {
+        let mut player = random_build(&mut rng);
+        let Some(mut id) = entries.choose(&mut rng).cloned() else {
+            break;
+        };
+        let mut seen = vec![];
+        let mut steps = 0;
+
+        let ending = loop {
+            let Some(dialogue) = dialogues.get(&id) else {
+                break "missing dialogue";
+            };
+            if !seen.contains(&id) {
+                seen.push(id.clone());
+            }
+            total_xp += player.enter(&format!("{}/{}", conversation, id), dialogue);
+            if steps == max_steps {
+                break "ran out of steps";
+            }
+
+            let options: Vec<(usize, _)> = dialogue
+                .options
+                .iter()
+                .enumerate()
+                .filter(|(_, option)| option.is_visible(|condition| player.has(condition)))
+                .collect();
+            let Some(&(index, option)) = options.choose(&mut rng) else {
+                break "stuck";
+            };
+            steps += 1;
+
+            let success = match player.challenge(option) {
+                Some((skill, value, target)) => {
+                    let success = challenge_succeeds(value, target, (rng.gen_range(1..=6), rng.gen_range(1..=6)));
+                    let skill = skill.to_lowercase();
+                    skills.entry(skill.clone()).or_default().entry(value).or_default().add(success);
+                    challenges
+                        .entry((id.clone(), index))
+                        .or_insert_with(|| (skill, target, Tally::default()))
+                        .2
+                        .add(success);
+                    success
+                }
+                None => true,
+            };
+            player.choose(option);
+
+            match option.outcome(success, conversation) {
+                Outcome::Dialogue(next) => id = next.to_string(),
+                Outcome::Link(_) => break "went to another conversation",
+                Outcome::Leave => break "left the conversation",
+                Outcome::MoveTo(_) => break "moved to another location",
+            }
+        };
+        total_steps += steps;
+        for id in seen {
+            *reached.entry(id).or_default() += 1;
+        }
+        *endings.entry(ending.to_string()).or_default() += 1;
+    }
+
+    let challenges = challenges
+        .into_iter()
+        .map(|((dialogue, option), (skill, target, tally))| ChallengeStats {
+            dialogue,
+            option,
+            skill,
+            target,
+            attempts: tally.attempts,
+            successes: tally.successes,
+            success_rate: rate(tally.successes, tally.attempts),
+        })
+        .collect();
+    let skills = skills
+        .into_iter()
+        .map(|(skill, by_value)| {
+            let attempts = by_value.values().map(|tally| tally.attempts).sum();
+            let successes = by_value.values().map(|tally| tally.successes).sum();
+            let rate_by_value: BTreeMap<i32, f64> =
+                by_value.iter().map(|(value, tally)| (*value, rate(tally.successes, tally.attempts))).collect();
+            let swing = match (rate_by_value.values().next(), rate_by_value.values().next_back()) {
+                (Some(lowest), Some(highest)) => highest - lowest,
+                _ => 0.0,
+            };
+            let stats = SkillStats {
+                attempts,
+                successes,
+                success_rate: rate(successes, attempts),
+                rate_by_value,
+                swing,
+            };
+            (skill, stats)
+        })
+        .collect();
+
+    PlaytestReport {
+        seed,
+        runs,
+        average_steps: rate(total_steps, runs),
+        average_xp: total_xp as f64 / runs.max(1) as f64,
+        reached,
+        challenges,
+        skills,
+        endings,
+    }
+}
+
+impl PlaytestReport {
+    // One table for spreadsheets: a row per dialogue, challenge, skill and ending, told apart by the first column
+    pub fn to_csv(&self) -> String {
+        let mut rows = vec!["kind,name,count,successes,rate".to_string()];
+        for (id, count) in &self.reached {
+            rows.push(format!("dialogue,{},{},,{:.4}", csv_field(id), count, rate(*count, self.runs)));
+        }
+        for challenge in &self.challenges {
+            let name = format!("{} option {} ({} {})", challenge.dialogue, challenge.option + 1, challenge.skill, challenge.target);
+            rows.push(format!(
+                "challenge,{},{},{},{:.4}",
+                csv_field(&name),
+                challenge.attempts,
+                challenge.successes,
+                challenge.success_rate
+            ));
+        }
+        for (skill, stats) in &self.skills {
+            rows.push(format!("skill,{},{},{},{:.4}", csv_field(skill), stats.attempts, stats.successes, stats.success_rate));
+        }
+        for (ending, count) in &self.endings {
+            rows.push(format!("ending,{},{},,{:.4}", csv_field(ending), count, rate(*count, self.runs)));
+        }
+        rows.push(format!("xp,average,{},,{:.4}", self.runs, self.average_xp));
+        rows.join("\n") + "\n"
+    }
+}
+
+// Quotes a field if it has a comma or quote in it
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+impl fmt::Display for PlaytestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} run(s) with seed {}", self.runs, self.seed)?;
+        writeln!(f, "  Average choices per run: {:.1}", self.average_steps)?;
+        writeln!(f, "  Average XP per run: {:.1}", self.average_xp)?;
+        writeln!(f, "Endings:")?;
+        for (ending, count) in &self.endings {
+            writeln!(f, "  {}: {:.1}%", ending, rate(*count, self.runs) * 100.0)?;
+        }
+        writeln!(f, "Dialogues reached:")?;
+        for (id, count) in &self.reached {
+            writeln!(f, "  {}: {:.1}%", id, rate(*count, self.runs) * 100.0)?;
+        }
+        writeln!(f, "Challenges:")?;
+        for challenge in &self.challenges {
+            writeln!(
+                f,
+                "  {} option {} ({} {}): {} of {} passed ({:.1}%)",
+                challenge.dialogue,
+                challenge.option + 1,
+                challenge.skill,
+                challenge.target,
+                challenge.successes,
+                challenge.attempts,
+                challenge.success_rate * 100.0
+            )?;
+        }
+        // Skills that swing outcomes the most first
+        let mut skills: Vec<(&String, &SkillStats)> = self.skills.iter().collect();
+        skills.sort_by(|a, b| b.1.swing.total_cmp(&a.1.swing).then(b.1.attempts.cmp(&a.1.attempts)));
+        writeln!(f, "Skills:")?;
+        for (skill, stats) in skills {
+            writeln!(
+                f,
+                "  {}: tested {} time(s), {:.1}% passed, {:+.1} percentage points from the lowest value tested to the highest",
+                skill,
+                stats.attempts,
+                stats.success_rate * 100.0,
+                stats.swing * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dialogue, DialogueOption};
+
+    fn go_to(next: Option<&str>) -> DialogueOption {
+        DialogueOption {
+            success_dialogue: next.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn dialogue(options: Vec<DialogueOption>) -> Dialogue {
+        Dialogue {
+            options,
+            ..Default::default()
+        }
+    }
+
+    // Start always leads to "Hall, north", which leads to End, which leaves
+    fn corridor() -> Dialogues {
+        let mut dialogues = Dialogues::new();
+        dialogues.insert("Start".to_string(), dialogue(vec![go_to(Some("Hall, north"))]));
+        dialogues.insert("Hall, north".to_string(), dialogue(vec![go_to(Some("End"))]));
+        dialogues.insert("End".to_string(), dialogue(vec![go_to(None)]));
+        dialogues
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_report() {
+        let mut dialogues = corridor();
+        let gamble = DialogueOption {
+            challenge_attribute: Some("Delusion".to_string()),
+            challenge_number: Some(10),
+            success_dialogue: Some("End".to_string()),
+            failure_dialogue: None,
+            ..Default::default()
+        };
+        dialogues["Start"].options.push(gamble);
+
+        let first = playtest("Test", &dialogues, 50, 7, DEFAULT_MAX_STEPS);
+        assert_eq!(first, playtest("Test", &dialogues, 50, 7, DEFAULT_MAX_STEPS));
+        assert_eq!(first.challenges[0].attempts, first.skills["delusion"].attempts);
+    }
+
+    #[test]
+    fn a_file_without_challenges_reaches_every_dialogue() {
+        let report = playtest("Test", &corridor(), 20, 1, DEFAULT_MAX_STEPS);
+        assert!(report.reached.values().all(|&count| count == 20));
+        assert_eq!(report.endings["left the conversation"], 20);
+        assert!(report.challenges.is_empty());
+    }
+
+    #[test]
+    fn csv_quotes_names_with_commas() {
+        let csv = playtest("Test", &corridor(), 1, 1, DEFAULT_MAX_STEPS).to_csv();
+        assert!(csv.contains("dialogue,\"Hall, north\",1,,1.0000\n"));
+        assert!(csv.contains("dialogue,Start,1,,1.0000\n"));
+    }
+}
@@ -0,0 +1,302 @@
+// The project file ties the game together: which locations exist and which dialogue file holds each conversation
+// Dialogue file paths are relative to the project file
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{initialize_dialogues, load_dialogues, Conversation, Dialogue, Dialogues, Exit, Location, RenameError};
+
+pub const DEFAULT_PROJECT_FILE: &str = "src/project.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Project {
+    pub start_location: String,
+    #[serde(default)]
+    pub locations: IndexMap<String, LocationEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct LocationEntry {
+    #[serde(default)]
+    pub conversations: IndexMap<String, String>, // Conversation ID to dialogue file
+    #[serde(default)]
+    pub exits: Vec<Exit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>, // Image path relative to the project file
+}
+
+// Something wrong with the location graph or the files it names
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProjectProblem {
+    MissingStart(String),
+    UnknownExit { from: String, to: String },
+    Unreachable(String),
+    MissingFile { conversation: String, path: String },
+    DuplicateConversation(String),
+}
+
+impl fmt::Display for ProjectProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectProblem::MissingStart(start) => write!(f, "Start location {} doesn't exist", start),
+            ProjectProblem::UnknownExit { from, to } if to.is_empty() => write!(f, "{} has an exit with no destination", from),
+            ProjectProblem::UnknownExit { from, to } => write!(f, "{} has an exit to unknown location {}", from, to),
+            ProjectProblem::Unreachable(location) => write!(f, "{} can't be reached from the start location", location),
+            ProjectProblem::MissingFile { conversation, path } => write!(f, "Conversation {} has no file at {}", conversation, path),
+            ProjectProblem::DuplicateConversation(conversation) => {
+                write!(f, "Conversation {} is attached to more than one location", conversation)
+            }
+        }
+    }
+}
+
+// One conversation of the project, with the dialogue file resolved against the project's folder
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConversationFile {
+    pub location: String,
+    pub conversation: String,
+    pub path: String,
+}
+
+impl Project {
+    pub fn load(project_path: &str) -> Result<Self, io::Error> {
+        let content = fs::read_to_string(project_path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, project_path: &str) -> Result<(), io::Error> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(project_path, json)
+    }
+
+    pub fn conversation_files(&self, project_path: &str) -> Vec<ConversationFile> {
+        let base = &Self::base_dir(project_path);
+        self.locations
+            .iter()
+            .flat_map(|(location, entry)| {
+                entry.conversations.iter().map(move |(conversation, file)| ConversationFile {
+                    location: location.clone(),
+                    conversation: conversation.clone(),
+                    path: base.join(file).to_string_lossy().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    // Which conversation a dialogue file belongs to, if the project knows it
+    pub fn conversation_for_file(&self, project_path: &str, file_path: &str) -> Option<String> {
+        let wanted = normalize_path(file_path);
+        self.conversation_files(project_path)
+            .into_iter()
+            .find(|file| normalize_path(&file.path) == wanted)
+            .map(|file| file.conversation)
+    }
+
+    // Every conversation's dialogues, read from disk
+    pub fn load_conversations(&self, project_path: &str) -> HashMap<String, Dialogues> {
+        self.conversation_files(project_path)
+            .into_iter()
+            .map(|file| {
+                let dialogues = initialize_dialogues(&file.path).map(|(dialogues, _)| dialogues).unwrap_or_default();
+                (file.conversation, dialogues)
+            })
+            .collect()
+    }
+
+    // Every conversation's dialogues as the game plays them, with defaults filled in
+    // The explorer, playtests and transcripts load through this so they see what the player sees
+    pub fn load_game_conversations(&self, project_path: &str) -> HashMap<String, Dialogues> {
+        let mut conversations = HashMap::new();
+        for file in self.conversation_files(project_path) {
+            if !Path::new(&file.path).exists() {
+                eprintln!("Missing dialogue file {} for conversation {}", file.path, file.conversation);
+                continue;
+            }
+            conversations.insert(file.conversation, load_dialogues(&file.path));
+        }
+        conversations
+    }
+
+    // Builds the runtime Location and Conversation structs from the files the project names
+    pub fn load_locations(&self, project_path: &str) -> HashMap<String, Location> {
+        let mut locations = HashMap::new();
+        for (name, entry) in &self.locations {
+            let mut location = Location::new(name.clone());
+            for exit in &entry.exits {
+                location.add_exit(exit.clone());
+            }
+            location.background = entry.background.clone();
+            locations.insert(name.clone(), location);
+        }
+        let mut dialogues = self.load_game_conversations(project_path);
+        for file in self.conversation_files(project_path) {
+            let Some(dialogues) = dialogues.remove(&file.conversation) else {
+                continue;
+            };
+            let mut conversation = Conversation::new(file.conversation.clone());
+            conversation.dialogues = dialogues;
+            if let Some(location) = locations.get_mut(&file.location) {
+                location.add_conversation(file.conversation, conversation);
+            }
+        }
+        locations
+    }
+}
+
+impl Project {
+    // The folder image and dialogue paths are relative to
+    pub fn base_dir(project_path: &str) -> PathBuf {
+        Path::new(project_path).parent().unwrap_or(Path::new("")).to_path_buf()
+    }
+
+    // The location a conversation is attached to
+    pub fn location_of(&self, conversation: &str) -> Option<&String> {
+        self.locations
+            .iter()
+            .find(|(_, entry)| entry.conversations.contains_key(conversation))
+            .map(|(name, _)| name)
+    }
+
+    pub fn location_names(&self) -> Vec<String> {
+        self.locations.keys().cloned().collect()
+    }
+
+    pub fn add_location(&mut self, name: &str) -> Result<(), RenameError> {
+        if name.is_empty() {
+            return Err(RenameError::EmptyId);
+        }
+        if self.locations.contains_key(name) {
+            return Err(RenameError::Collision(name.to_string()));
+        }
+        self.locations.insert(name.to_string(), LocationEntry::default());
+        if self.start_location.is_empty() {
+            self.start_location = name.to_string();
+        }
+        Ok(())
+    }
+
+    // Renames in place, along with every exit leading there and the start location
+    pub fn rename_location(&mut self, old_name: &str, new_name: &str) -> Result<(), RenameError> {
+        if new_name.is_empty() {
+            return Err(RenameError::EmptyId);
+        }
+        if old_name == new_name {
+            return Ok(());
+        }
+        if self.locations.contains_key(new_name) {
+            return Err(RenameError::Collision(new_name.to_string()));
+        }
+        let Some(index) = self.locations.get_index_of(old_name) else {
+            return Err(RenameError::NotFound(old_name.to_string()));
+        };
+        let (_, entry) = self.locations.shift_remove_index(index).unwrap_or_default();
+        self.locations.shift_insert(index, new_name.to_string(), entry);
+
+        for exit in self.locations.values_mut().flat_map(|entry| entry.exits.iter_mut()) {
+            if exit.to == old_name {
+                exit.to = new_name.to_string();
+            }
+        }
+        if self.start_location == old_name {
+            self.start_location = new_name.to_string();
+        }
+        Ok(())
+    }
+
+    // Removes the location and every exit that led to it
+    pub fn remove_location(&mut self, name: &str) -> Option<LocationEntry> {
+        let entry = self.locations.shift_remove(name)?;
+        for other in self.locations.values_mut() {
+            other.exits.retain(|exit| exit.to != name);
+        }
+        Some(entry)
+    }
+
+    // Locations the player can walk to from the start location, ignoring exit conditions
+    pub fn reachable_locations(&self) -> HashSet<String> {
+        let mut reached = HashSet::new();
+        let mut queue = VecDeque::from([self.start_location.clone()]);
+        while let Some(name) = queue.pop_front() {
+            let Some(entry) = self.locations.get(&name) else {
+                continue;
+            };
+            if !reached.insert(name) {
+                continue;
+            }
+            queue.extend(entry.exits.iter().map(|exit| exit.to.clone()));
+        }
+        reached
+    }
+
+    // Exits that lead nowhere, locations the start can't reach and conversation files that are missing
+    pub fn validate(&self, project_path: &str) -> Vec<ProjectProblem> {
+        let mut problems = vec![];
+        if !self.locations.contains_key(&self.start_location) {
+            problems.push(ProjectProblem::MissingStart(self.start_location.clone()));
+        }
+        for (name, entry) in &self.locations {
+            for exit in &entry.exits {
+                if !self.locations.contains_key(&exit.to) {
+                    problems.push(ProjectProblem::UnknownExit {
+                        from: name.clone(),
+                        to: exit.to.clone(),
+                    });
+                }
+            }
+        }
+        let reachable = self.reachable_locations();
+        if !reachable.is_empty() {
+            for name in self.locations.keys().filter(|name| !reachable.contains(*name)) {
+                problems.push(ProjectProblem::Unreachable(name.clone()));
+            }
+        }
+        // Links name conversations without their location, so the IDs must be unique across the project
+        let mut seen = HashSet::new();
+        for file in self.conversation_files(project_path) {
+            if !seen.insert(file.conversation.clone()) {
+                problems.push(ProjectProblem::DuplicateConversation(file.conversation.clone()));
+            }
+            if !Path::new(&file.path).exists() {
+                problems.push(ProjectProblem::MissingFile {
+                    conversation: file.conversation,
+                    path: file.path,
+                });
+            }
+        }
+        problems
+    }
+}
+
+fn normalize_path(path: &str) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+}
+
+// A link names a dialogue in the same file ("ClockBroken") or in another conversation ("Intro/Start")
+pub fn split_link(link: &str) -> (Option<&str>, &str) {
+    match link.split_once('/') {
+        Some((conversation, dialogue)) => (Some(conversation), dialogue),
+        None => (None, link),
+    }
+}
+
+// Links from this dialogue that don't resolve, either locally or in the other conversations of the project
+pub fn broken_links<'a>(
+    dialogue: &'a Dialogue,
+    local: &Dialogues,
+    conversations: &HashMap<String, &Dialogues>,
+) -> Vec<&'a str> {
+    dialogue
+        .links()
+        .filter(|link| match split_link(link) {
+            (None, id) => !local.contains_key(id),
+            (Some(conversation), id) => !conversations.get(conversation).is_some_and(|dialogues| dialogues.contains_key(id)),
+        })
+        .collect()
+}
@@ -0,0 +1,289 @@
+// One step of the game at a time: what the player sees, and what picking an option, talking or travelling does
+// The GUI, the terminal game and the transcripts all play through Game, so they can't drift apart
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::localization::{localize, Language};
+use crate::navigation::{NavigationError, Position};
+use crate::player::{challenge_succeeds, Player};
+use crate::{Dialogue, DialogueOption, Exit, Location};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Time {
+    pub day: i32,
+    pub hour: i32,
+    pub minute: i32,
+}
+
+impl Default for Time {
+    // The game starts at half past three in the morning
+    fn default() -> Self {
+        Self { day: 1, hour: 3, minute: 30 }
+    }
+}
+
+impl Time {
+    // Carries whole hours into the hour and whole days into the day, so 23:30 plus 90 minutes is 01:00 the next day
+    pub fn increase(&mut self, added_minutes: i32) {
+        let minutes = self.hour * 60 + self.minute + added_minutes;
+        self.day += minutes / 1440;
+        self.hour = minutes % 1440 / 60;
+        self.minute = minutes % 60;
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Day {}, {:02}:{:02}", self.day, self.hour, self.minute)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Visits {
+    pub dialogues: HashMap<String, i32>, // By "Conv/Id"
+    pub locations: HashMap<String, i32>,
+}
+
+impl Visits {
+    // Counts the dialogue the player is now at, and the location too if they just arrived there
+    pub fn record(&mut self, position: &Position, arrived: bool) {
+        if arrived {
+            *self.locations.entry(position.location.clone()).or_default() += 1;
+        }
+        if let (Some(conversation), Some(dialogue)) = (&position.conversation, &position.dialogue) {
+            *self.dialogues.entry(format!("{}/{}", conversation, dialogue)).or_default() += 1;
+        }
+    }
+}
+
+// A challenge as it was rolled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roll {
+    pub skill: String,
+    pub target: i32,
+    pub value: i32, // The player's skill
+    pub dice: (i32, i32),
+    pub success: bool,
+}
+
+impl fmt::Display for Roll {
+    // "delusion 10: 1 + 1 + 3 = 5, failed"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (die1, die2) = self.dice;
+        let result = if self.success { "passed" } else { "failed" };
+        write!(
+            f,
+            "{} {}: {} + {} + {} = {}, {}",
+            self.skill,
+            self.target,
+            die1,
+            die2,
+            self.value,
+            die1 + die2 + self.value,
+            result
+        )
+    }
+}
+
+// What one move changed, for the caller to show
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Turn {
+    pub roll: Option<Roll>,
+    pub flags: Vec<String>, // Only the ones the player didn't have yet
+    pub items: Vec<String>,
+    pub xp: i32,           // For reaching a dialogue the first time
+    pub skill_points: i32, // Gained from that XP
+    pub minutes: i32,
+}
+
+pub struct Game {
+    pub locations: HashMap<String, Location>,
+    pub position: Position,
+    pub player: Player,
+    pub time: Time,
+    pub visits: Visits,
+    pub language: Option<Language>, // None plays in the language the dialogues are written in
+}
+
+impl Game {
+    // Call start once the game is set up, to count the starting place as reached
+    pub fn new(locations: HashMap<String, Location>, position: Position, player: Player) -> Self {
+        Self {
+            locations,
+            position,
+            player,
+            time: Time::default(),
+            visits: Visits::default(),
+            language: None,
+        }
+    }
+
+    pub fn start(&mut self) -> Turn {
+        self.reached(true)
+    }
+
+    // The current dialogue with its text in the player's language, and only the options the player can see
+    pub fn dialogue(&self) -> Option<Cow<'_, Dialogue>> {
+        let dialogue = self.position.current_dialogue(&self.locations)?;
+        let (conversation, id) = (self.position.conversation.as_deref()?, self.position.dialogue.as_deref()?);
+        let mut dialogue = localize(self.language.as_ref(), conversation, id, dialogue);
+        let has = |condition: &str| self.player.has(condition);
+        if !dialogue.options.iter().all(|option| option.is_visible(has)) {
+            dialogue.to_mut().options.retain(|option| option.is_visible(has));
+        }
+        Some(dialogue)
+    }
+
+    // Values for the variables in dialogue text; see markup::is_known_variable for the names
+    pub fn variable(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "visits" => {
+                let key = format!("{}/{}", self.position.conversation.as_ref()?, self.position.dialogue.as_ref()?);
+                self.visits.dialogues.get(&key).copied().unwrap_or(0)
+            }
+            "location.visits" => self.visits.locations.get(&self.position.location).copied().unwrap_or(0),
+            "time.day" => self.time.day,
+            "time.hour" => self.time.hour,
+            "time.minute" => self.time.minute,
+            _ => return self.player.variable(name),
+        };
+        Some(value.to_string())
+    }
+
+    // Exits the player can take from here
+    pub fn exits(&self) -> Vec<&Exit> {
+        self.position
+            .current_location(&self.locations)
+            .map(|location| location.available_exits(|condition| self.player.has(condition)))
+            .unwrap_or_default()
+    }
+
+    pub fn conversations(&self) -> Vec<&String> {
+        self.position
+            .current_location(&self.locations)
+            .map(Location::conversation_ids)
+            .unwrap_or_default()
+    }
+
+    pub fn travel(&mut self, to: &str) -> Result<Turn, NavigationError> {
+        let player = &self.player;
+        let minutes = self.position.travel(&self.locations, to, |condition| player.has(condition))?;
+        self.time.increase(minutes);
+        Ok(Turn {
+            minutes,
+            ..self.reached(true)
+        })
+    }
+
+    pub fn talk(&mut self, conversation: &str) -> Result<Turn, NavigationError> {
+        self.position.enter_conversation(&self.locations, conversation)?;
+        Ok(self.reached(false))
+    }
+
+    // Jumps straight to "Conv/Id", like a transcript starting partway into a conversation
+    pub fn go_to(&mut self, link: &str) -> Result<Turn, NavigationError> {
+        let location = self.position.location.clone();
+        self.position.follow_link(&self.locations, link)?;
+        Ok(self.reached(self.position.location != location))
+    }
+
+    // Picks an option of the current dialogue, rolling for it if it's a challenge
+    // Nothing changes if the option leads nowhere, so the player can pick another
+    pub fn choose(&mut self, option: &DialogueOption, roll: impl FnOnce() -> (i32, i32)) -> Result<Turn, NavigationError> {
+        let roll = self.player.challenge(option).map(|(skill, value, target)| {
+            let dice = roll();
+            Roll {
+                skill: skill.to_string(),
+                target,
+                value,
+                dice,
+                success: challenge_succeeds(value, target, dice),
+            }
+        });
+        let success = roll.as_ref().is_none_or(|roll| roll.success);
+
+        let location = self.position.location.clone();
+        let minutes = self.position.choose_option(&self.locations, option, success)?;
+        let flags = option.flags.iter().flatten().filter(|flag| !self.player.flags.contains(*flag)).cloned().collect();
+        let items = option.item_to_pickup.iter().filter(|item| !self.player.items.contains(item)).cloned().collect();
+        self.player.choose(option);
+        self.time.increase(minutes);
+        Ok(Turn {
+            roll,
+            flags,
+            items,
+            minutes,
+            ..self.reached(self.position.location != location)
+        })
+    }
+
+    // Counts a visit to wherever the player just got to, and gives the dialogue's XP the first time
+    fn reached(&mut self, arrived: bool) -> Turn {
+        self.visits.record(&self.position, arrived);
+        let (Some(conversation), Some(id)) = (&self.position.conversation, &self.position.dialogue) else {
+            return Turn::default();
+        };
+        let Some(dialogue) = self.position.current_dialogue(&self.locations) else {
+            return Turn::default();
+        };
+        let skill_points = self.player.skill_points;
+        let xp = self.player.enter(&format!("{}/{}", conversation, id), dialogue);
+        Turn {
+            xp,
+            skill_points: self.player.skill_points - skill_points,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Conversation;
+
+    #[test]
+    fn time_rolls_over_into_the_next_day() {
+        let mut time = Time { day: 1, hour: 23, minute: 30 };
+        time.increase(90);
+        assert_eq!(time, Time { day: 2, hour: 1, minute: 0 });
+        time.increase(1440 + 59);
+        assert_eq!(time, Time { day: 3, hour: 1, minute: 59 });
+    }
+
+    #[test]
+    fn a_challenge_is_rolled_once_and_its_outcome_followed() {
+        let gamble = DialogueOption {
+            challenge_attribute: Some("Delusion".to_string()),
+            challenge_number: Some(10),
+            success_dialogue: Some("Won".to_string()),
+            failure_dialogue: Some("Lost".to_string()),
+            flags: Some(vec!["gambled".to_string()]),
+            ..Default::default()
+        };
+        let mut clock = Conversation::new("Clock".to_string());
+        clock.add_dialogue("Start".to_string(), Dialogue {
+            options: vec![gamble.clone()],
+            ..Default::default()
+        });
+        clock.add_dialogue("Won".to_string(), Dialogue::default());
+        clock.add_dialogue("Lost".to_string(), Dialogue {
+            xp_reward: Some(10),
+            ..Default::default()
+        });
+        let mut hall = Location::new("Hall".to_string());
+        hall.add_conversation("Clock".to_string(), clock);
+        let locations = [("Hall".to_string(), hall)].into_iter().collect();
+
+        let mut game = Game::new(locations, Position::at("Hall"), Player::with_build(3, 3, 3, 3));
+        game.start();
+        game.talk("Clock").unwrap();
+        let turn = game.choose(&gamble, || (1, 1)).unwrap();
+        assert!(!turn.roll.unwrap().success);
+        assert_eq!(turn.flags, vec!["gambled"]);
+        assert_eq!(turn.xp, 10);
+        assert_eq!(game.position.dialogue.as_deref(), Some("Lost"));
+        assert_eq!(game.variable("visits").as_deref(), Some("1"));
+    }
+}
@@ -0,0 +1,129 @@
+// How a dialogue looks in-game: background, portrait, the speaker's name and the text
+// Shared by the game and the editor's preview pane so the two can't drift apart
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use eframe::egui;
+use egui::text::{LayoutJob, TextFormat};
+
+use crate::markup::{append_markup, layout_job, MarkupCache, MarkupContext};
+use crate::speakers::{find_speaker, speaker_label, Speakers};
+use crate::Dialogue;
+
+const BACKGROUND_HEIGHT: f32 = 240.0;
+const PORTRAIT_SIZE: f32 = 128.0;
+
+// Textures loaded from the project folder, each read from disk once
+// A file that fails to load is remembered too, so a bad path is reported once instead of every frame
+pub struct ImageCache {
+    base: PathBuf,
+    textures: HashMap<String, Option<egui::TextureHandle>>,
+}
+
+impl ImageCache {
+    pub fn new(base: PathBuf) -> Self {
+        Self {
+            base,
+            textures: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, ctx: &egui::Context, path: &str) -> Option<egui::TextureHandle> {
+        if let Some(texture) = self.textures.get(path) {
+            return texture.clone();
+        }
+
+        let full_path = self.base.join(path);
+        let texture = match image::open(&full_path) {
+            Ok(image) => {
+                let image = image.to_rgba8();
+                let size = [image.width() as usize, image.height() as usize];
+                let pixels = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+                Some(ctx.load_texture(path, pixels, egui::TextureOptions::LINEAR))
+            }
+            Err(err) => {
+                eprintln!("Couldn't load image {}: {}", full_path.display(), err);
+                None
+            }
+        };
+        self.textures.insert(path.to_string(), texture.clone());
+        texture
+    }
+
+    // Forget everything, so edited images are read again
+    pub fn clear(&mut self) {
+        self.textures.clear();
+    }
+}
+
+// The dialogue's own images win over its location's background and its speaker's portrait
+pub fn scene_images<'a>(
+    dialogue: &'a Dialogue,
+    location_background: Option<&'a str>,
+    speakers: &'a Speakers,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let background = dialogue.background.as_deref().or(location_background);
+    let portrait = dialogue
+        .portrait
+        .as_deref()
+        .or_else(|| find_speaker(speakers, &dialogue.speaker)?.portrait.as_deref());
+    (background, portrait)
+}
+
+// A background across the top of the view, scaled to fit
+pub fn show_background(ui: &mut egui::Ui, images: &mut ImageCache, path: Option<&str>) {
+    if let Some(texture) = path.and_then(|path| images.get(ui.ctx(), path)) {
+        ui.add(egui::Image::new(&texture).max_height(BACKGROUND_HEIGHT).max_width(ui.available_width()));
+    }
+}
+
+// Draws the dialogue and its options; returns the index of the option clicked, if any
+// With interactive off the options are shown greyed out, as the editor's preview does
+pub fn show_dialogue(
+    ui: &mut egui::Ui,
+    images: &mut ImageCache,
+    markup: &mut MarkupCache,
+    dialogue: &Dialogue,
+    location_background: Option<&str>,
+    context: &MarkupContext,
+    interactive: bool,
+) -> Option<usize> {
+    let (background, portrait) = scene_images(dialogue, location_background, context.speakers);
+
+    show_background(ui, images, background);
+
+    let style = ui.style().clone();
+    ui.horizontal_top(|ui| {
+        if let Some(texture) = portrait.and_then(|path| images.get(ui.ctx(), path)) {
+            ui.add(egui::Image::new(&texture).max_size(egui::vec2(PORTRAIT_SIZE, PORTRAIT_SIZE)));
+        }
+        ui.vertical(|ui| {
+            // The speaker's name in their color; narration has no name
+            if let Some(name) = speaker_label(context.speakers, &dialogue.speaker) {
+                ui.label(name);
+            }
+            ui.label(layout_job(markup.get(&dialogue.intro), context, &style));
+        });
+    });
+
+    ui.separator();
+    let mut chosen = None;
+    for (index, option) in dialogue.options.iter().enumerate() {
+        let prefix = match &option.challenge_attribute {
+            Some(skill) => format!("{}. [{} {}] ", index + 1, skill, option.challenge_number.unwrap_or(0)),
+            None => format!("{}. ", index + 1),
+        };
+        let mut job = LayoutJob::default();
+        job.append(
+            &prefix,
+            0.0,
+            TextFormat::simple(egui::TextStyle::Body.resolve(&style), style.visuals.text_color()),
+        );
+        append_markup(&mut job, markup.get(&option.description), context, &style);
+        if ui.add_enabled(interactive, egui::Button::new(job)).clicked() {
+            chosen = Some(index);
+        }
+    }
+    chosen
+}
@@ -0,0 +1,493 @@
+// JSON Schemas for the files writers edit by hand: dialogue files, the project file and the speaker catalogue
+// The schemas are built here and shipped in schema/, for editors that check JSON as you type; a test keeps
+// the shipped files and the structs in step. `validate` checks files against them before the content checks,
+// with a small validator for the parts of JSON Schema used here
+//
+// Optional text fields accept "" and null as well as a missing key, since older files write unset fields that way
+
+use std::fmt;
+use std::fs;
+use std::io;
+
+use serde_json::{json, Map, Value};
+
+use crate::project::Project;
+use crate::speakers::speaker_path;
+use crate::SKILLS;
+
+pub const SCHEMA_DIR: &str = "schema";
+pub const DIALOGUE_SCHEMA_FILE: &str = "schema/dialogues.schema.json";
+pub const PROJECT_SCHEMA_FILE: &str = "schema/project.schema.json";
+pub const SPEAKER_SCHEMA_FILE: &str = "schema/speakers.schema.json";
+
+const ID: &str = "https://shadow-soldiers.invalid/schema/";
+
+fn optional_string(description: &str) -> Value {
+    json!({ "type": ["string", "null"], "description": description })
+}
+
+fn optional_integer(description: &str) -> Value {
+    json!({ "type": ["integer", "null"], "description": description })
+}
+
+fn skill(description: &str, optional: bool) -> Value {
+    let mut values: Vec<Value> = SKILLS.iter().map(|skill| json!(skill)).collect();
+    let types = if optional {
+        values.push(json!(""));
+        values.push(Value::Null);
+        json!(["string", "null"])
+    } else {
+        json!("string")
+    };
+    json!({ "type": types, "enum": values, "description": description })
+}
+
+pub fn dialogue_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("{}dialogues.schema.json", ID),
+        "title": "Dialogue file",
+        "description": "Dialogues by ID. Links name a dialogue in the same file, or \"Conversation/Id\" in another one",
+        "type": "object",
+        "additionalProperties": { "$ref": "#/$defs/Dialogue" },
+        "$defs": {
+            "Dialogue": {
+                "type": "object",
+                "required": ["intro"],
+                "additionalProperties": false,
+                "properties": {
+                    "speaker": { "type": "string", "description": "Speaker ID from the catalogue; empty for narration" },
+                    "intro": { "type": "string", "description": "What the speaker says, with markup" },
+                    "options": { "type": "array", "items": { "$ref": "#/$defs/DialogueOption" } },
+                    "passive_check": { "type": "array", "items": { "$ref": "#/$defs/PassiveCheck" } },
+                    "xp_reward": optional_integer("XP given the first time the player reaches this dialogue"),
+                    "is_hidden": { "type": "boolean" },
+                    "time": optional_integer("Minutes the dialogue takes"),
+                    "background": optional_string("Image path relative to the project folder, replacing the location's"),
+                    "portrait": optional_string("Image path relative to the project folder, replacing the speaker's")
+                }
+            },
+            "DialogueOption": {
+                "type": "object",
+                "required": ["description"],
+                "additionalProperties": false,
+                "properties": {
+                    "description": { "type": "string", "description": "The option's text, with markup" },
+                    "challenge_attribute": skill("Skill the option challenges", true),
+                    "challenge_number": optional_integer("Number two dice plus the skill have to reach"),
+                    "success_dialogue": optional_string("Where the option leads, or where a passed challenge leads"),
+                    "failure_dialogue": optional_string("Where a failed challenge leads"),
+                    "item_to_pickup": optional_string("Item the player picks up by choosing the option"),
+                    "visible_when": optional_string("Flag or item the player needs to see the option"),
+                    "flags": {
+                        "type": ["array", "null"],
+                        "items": { "type": "string" },
+                        "description": "Flags set by choosing the option"
+                    },
+                    "move_to": optional_string("Location the player walks to when choosing the option")
+                }
+            },
+            "PassiveCheck": {
+                "type": "object",
+                "required": ["skill", "target"],
+                "additionalProperties": false,
+                "properties": {
+                    "skill": skill("Skill to check", false),
+                    "target": { "type": "integer", "description": "Number to check against" },
+                    "success_text": optional_string("Text shown on success"),
+                    "failure_text": optional_string("Text shown on failure"),
+                    "speaker": optional_string("Speaker ID for both texts")
+                }
+            }
+        }
+    })
+}
+
+pub fn project_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("{}project.schema.json", ID),
+        "title": "Project file",
+        "type": "object",
+        "required": ["start_location"],
+        "additionalProperties": false,
+        "properties": {
+            "start_location": { "type": "string" },
+            "locations": { "type": "object", "additionalProperties": { "$ref": "#/$defs/Location" } }
+        },
+        "$defs": {
+            "Location": {
+                "type": "object",
+                "additionalProperties": false,
+                "properties": {
+                    "conversations": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                        "description": "Conversation ID to dialogue file, relative to the project file"
+                    },
+                    "exits": { "type": "array", "items": { "$ref": "#/$defs/Exit" } },
+                    "background": optional_string("Image path relative to the project file")
+                }
+            },
+            "Exit": {
+                "type": "object",
+                "required": ["to"],
+                "additionalProperties": false,
+                "properties": {
+                    "to": { "type": "string", "description": "Location the exit leads to" },
+                    "condition": optional_string("Flag or item the player needs before the exit is offered"),
+                    "time_cost": optional_integer("Minutes it takes to get there")
+                }
+            }
+        }
+    })
+}
+
+pub fn speaker_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("{}speakers.schema.json", ID),
+        "title": "Speaker catalogue",
+        "type": "object",
+        "additionalProperties": { "$ref": "#/$defs/Speaker" },
+        "$defs": {
+            "Speaker": {
+                "type": "object",
+                "required": ["name"],
+                "additionalProperties": false,
+                "properties": {
+                    "name": { "type": "string" },
+                    "kind": { "type": "string", "enum": ["character", "skill", "narrator"] },
+                    "color": optional_string("\"#rrggbb\""),
+                    "portrait": optional_string("Image path relative to the project folder")
+                }
+            }
+        }
+    })
+}
+
+// Every schema with the file it's shipped as
+pub fn schemas() -> Vec<(&'static str, Value)> {
+    vec![
+        (DIALOGUE_SCHEMA_FILE, dialogue_schema()),
+        (PROJECT_SCHEMA_FILE, project_schema()),
+        (SPEAKER_SCHEMA_FILE, speaker_schema()),
+    ]
+}
+
+pub fn schema_json(schema: &Value) -> String {
+    let mut json = serde_json::to_string_pretty(schema).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+// Writes the shipped schema files from the code above
+pub fn write_schemas() -> Result<(), io::Error> {
+    fs::create_dir_all(SCHEMA_DIR)?;
+    for (path, schema) in schemas() {
+        fs::write(path, schema_json(&schema))?;
+    }
+    Ok(())
+}
+
+// What a file is to the project, which decides the schema it's checked against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileKind {
+    Project,
+    Dialogues,
+    Speakers,
+}
+
+impl FileKind {
+    pub fn schema(self) -> Value {
+        match self {
+            FileKind::Project => project_schema(),
+            FileKind::Dialogues => dialogue_schema(),
+            FileKind::Speakers => speaker_schema(),
+        }
+    }
+}
+
+// The project file, every dialogue file it names and the speaker catalogue, each with its kind
+// A file is a dialogue file because the project lists it as a conversation, whatever it's called
+pub fn project_files(project_path: &str) -> Vec<(String, FileKind)> {
+    let mut files = vec![(project_path.to_string(), FileKind::Project)];
+    if let Ok(project) = Project::load(project_path) {
+        for file in project.conversation_files(project_path) {
+            if !files.iter().any(|(path, _)| *path == file.path) {
+                files.push((file.path, FileKind::Dialogues));
+            }
+        }
+    }
+    files.push((speaker_path(project_path), FileKind::Speakers));
+    files
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub path: String, // JSON path like $.ClockInspect.options[0].challenge_number
+    pub message: String,
+    pub unknown_field: bool, // A key the schema doesn't know, most likely a typo
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(number) if number.is_i64() || number.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::String(key.to_string()))
+    }
+}
+
+// Resolves "#/$defs/Name" against the root schema
+fn resolve<'a>(schema: &'a Value, root: &'a Value) -> &'a Value {
+    match schema.get("$ref").and_then(Value::as_str).and_then(|reference| reference.strip_prefix("#/")) {
+        Some(pointer) => root.pointer(&format!("/{}", pointer)).unwrap_or(&Value::Null),
+        None => schema,
+    }
+}
+
+fn check(value: &Value, schema: &Value, root: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let schema = resolve(schema, root);
+    let mut violation = |message: String| {
+        violations.push(Violation {
+            path: path.to_string(),
+            message,
+            unknown_field: false,
+        })
+    };
+
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            other => other.as_str().into_iter().collect(),
+        };
+        let actual = type_name(value);
+        let integer_as_number = actual == "integer" && allowed.contains(&"number");
+        if !allowed.contains(&actual) && !integer_as_number {
+            violation(format!("expected {}, found {}", allowed.join(" or "), actual));
+            return;
+        }
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            let shown: Vec<String> = values.iter().filter(|value| !value.is_null()).map(Value::to_string).collect();
+            violation(format!("{} isn't one of {}", value, shown.join(", ")));
+        }
+    }
+
+    match value {
+        Value::Object(object) => check_object(object, schema, root, path, violations),
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item, item_schema, root, &format!("{}[{}]", path, i), violations);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(object: &Map<String, Value>, schema: &Value, root: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for required in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+        if !object.contains_key(required) {
+            violations.push(Violation {
+                path: path.to_string(),
+                message: format!("missing required field '{}'", required),
+                unknown_field: false,
+            });
+        }
+    }
+    for (key, value) in object {
+        let key_path = child_path(path, key);
+        match (properties.and_then(|properties| properties.get(key)), schema.get("additionalProperties")) {
+            (Some(property), _) => check(value, property, root, &key_path, violations),
+            (None, Some(Value::Bool(false))) => {
+                let known: Vec<&str> = properties.into_iter().flat_map(|properties| properties.keys()).map(String::as_str).collect();
+                let message = match known.iter().find(|field| edit_distance(key, field) <= 2) {
+                    Some(field) => format!("unknown field; did you mean '{}'?", field),
+                    None => format!("unknown field; expected one of {}", known.join(", ")),
+                };
+                violations.push(Violation {
+                    path: key_path,
+                    message,
+                    unknown_field: true,
+                });
+            }
+            (None, Some(additional)) if additional.is_object() => check(value, additional, root, &key_path, violations),
+            (None, _) => {}
+        }
+    }
+}
+
+// Levenshtein distance, for suggesting the field a typo was meant to be
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Everything in the value that breaks the schema
+pub fn validate(value: &Value, schema: &Value) -> Vec<Violation> {
+    let mut violations = vec![];
+    check(value, schema, schema, "$", &mut violations);
+    violations
+}
+
+// Checks a single dialogue of a dialogue file, with paths relative to it, like options[0].sucess_dialogue
+pub fn dialogue_violations(dialogue: &Value) -> Vec<Violation> {
+    let schema = dialogue_schema();
+    let mut violations = vec![];
+    check(dialogue, &schema["$defs"]["Dialogue"], &schema, "", &mut violations);
+    for violation in violations.iter_mut() {
+        violation.path = violation.path.trim_start_matches('.').to_string();
+    }
+    violations
+}
+
+// Reads and checks a file; a file that isn't JSON at all is one violation at its root
+pub fn validate_file(path: &str, kind: FileKind) -> Result<Vec<Violation>, io::Error> {
+    let content = fs::read_to_string(path)?;
+    match serde_json::from_str::<Value>(&content) {
+        Ok(value) => Ok(validate(&value, &kind.schema())),
+        Err(err) => Ok(vec![Violation {
+            path: "$".to_string(),
+            message: format!("not valid JSON: {}", err),
+            unknown_field: false,
+        }]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{LocationEntry, DEFAULT_PROJECT_FILE};
+    use crate::speakers::{Speaker, SpeakerKind};
+    use crate::{Dialogue, DialogueOption, Dialogues, Exit, PassiveCheck};
+
+    // If this fails after changing a struct or the schema code, run `cargo run -- schema` to rewrite the files
+    #[test]
+    fn shipped_schemas_match_the_code() {
+        for (path, schema) in schemas() {
+            let shipped = fs::read_to_string(path).unwrap_or_default();
+            assert!(shipped == schema_json(&schema), "{} is out of date; run `cargo run -- schema`", path);
+        }
+    }
+
+    // Every field the structs write is in the schema, so a new field can't be forgotten there
+    #[test]
+    fn schemas_accept_every_field() {
+        let option = DialogueOption {
+            description: "Go".to_string(),
+            challenge_attribute: Some("gunsmoke".to_string()),
+            challenge_number: Some(8),
+            success_dialogue: Some("Next".to_string()),
+            failure_dialogue: Some("Other/Start".to_string()),
+            item_to_pickup: Some("Key".to_string()),
+            visible_when: Some("met".to_string()),
+            flags: Some(vec!["met".to_string()]),
+            move_to: Some("FirstFloor".to_string()),
+        };
+        let check = PassiveCheck {
+            skill: "gizmo".to_string(),
+            target: 10,
+            success_text: Some("Yes".to_string()),
+            failure_text: Some("No".to_string()),
+            speaker: Some("Gizmo".to_string()),
+        };
+        let dialogue = Dialogue {
+            speaker: "Clock".to_string(),
+            intro: "Tick.".to_string(),
+            options: vec![option],
+            passive_check: vec![check],
+            xp_reward: Some(5),
+            is_hidden: true,
+            time: Some(1),
+            background: Some("hall.png".to_string()),
+            portrait: Some("clock.png".to_string()),
+        };
+        let dialogues: Dialogues = [("Start".to_string(), dialogue)].into_iter().collect();
+        assert_eq!(validate(&serde_json::to_value(&dialogues).unwrap(), &dialogue_schema()), vec![]);
+
+        let mut project = Project {
+            start_location: "Hall".to_string(),
+            ..Default::default()
+        };
+        project.locations.insert(
+            "Hall".to_string(),
+            LocationEntry {
+                conversations: [("Intro".to_string(), "intro.json".to_string())].into_iter().collect(),
+                exits: vec![Exit {
+                    to: "Hall".to_string(),
+                    condition: Some("key".to_string()),
+                    time_cost: Some(1),
+                }],
+                background: Some("hall.png".to_string()),
+            },
+        );
+        assert_eq!(validate(&serde_json::to_value(&project).unwrap(), &project_schema()), vec![]);
+
+        let speaker = Speaker {
+            name: "Clock".to_string(),
+            kind: SpeakerKind::Narrator,
+            color: Some("#ffffff".to_string()),
+            portrait: Some("clock.png".to_string()),
+        };
+        let speakers = json!({ "Clock": serde_json::to_value(&speaker).unwrap() });
+        assert_eq!(validate(&speakers, &speaker_schema()), vec![]);
+    }
+
+    #[test]
+    fn violations_name_the_path() {
+        let file = json!({
+            "Start": {
+                "speaker": "",
+                "intro": "Hi",
+                "options": [{ "description": "Go", "sucess_dialogue": "Next", "challenge_number": "high" }]
+            },
+            "Next": { "speaker": "" }
+        });
+        let found: Vec<String> = validate(&file, &dialogue_schema()).into_iter().map(|violation| violation.to_string()).collect();
+        assert_eq!(found.len(), 3);
+        let has = |text: &str| found.iter().any(|violation| violation.starts_with(text));
+        assert!(has("$.Start.options[0].challenge_number: expected integer or null, found string"));
+        assert!(has("$.Start.options[0].sucess_dialogue: unknown field; did you mean 'success_dialogue'?"));
+        assert!(has("$.Next: missing required field 'intro'"));
+    }
+
+    #[test]
+    fn content_files_pass() {
+        let files = project_files(DEFAULT_PROJECT_FILE);
+        assert!(files.contains(&("src/dialogues/clock.json".to_string(), FileKind::Dialogues)));
+        for (path, kind) in files {
+            assert_eq!(validate_file(&path, kind).unwrap(), vec![], "{}", path);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// The speaker catalogue: everyone and everything that talks, referenced from dialogues by ID
+// An empty speaker ID is narration and uses the catalogue's narrator, if it has one
+
+use std::fs;
+use std::io;
+
+use eframe::egui;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::project::Project;
+use crate::Dialogue;
+
+pub const SPEAKER_FILE: &str = "speakers.json";
+
+// The catalogue sits next to the project file, so src/project.json goes with src/speakers.json
+pub fn speaker_path(project_path: &str) -> String {
+    Project::base_dir(project_path).join(SPEAKER_FILE).to_string_lossy().into_owned()
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeakerKind {
+    #[default]
+    Character,
+    Skill, // The player's skills chiming in, mostly through passive checks
+    Narrator,
+}
+
+// Unset fields are left out of the catalogue file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Speaker {
+    pub name: String, // Shown to the player
+    #[serde(default)]
+    pub kind: SpeakerKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>, // "#rrggbb"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub portrait: Option<String>, // Image path relative to the project folder
+}
+
+impl Speaker {
+    pub fn text_color(&self) -> Option<egui::Color32> {
+        egui::Color32::from_hex(self.color.as_deref()?).ok()
+    }
+}
+
+// Speakers by ID, in the order the catalogue lists them
+pub type Speakers = IndexMap<String, Speaker>;
+
+// A missing catalogue means no speakers yet; a broken one is reported and treated the same
+pub fn load_speakers(file_path: &str) -> Speakers {
+    match fs::read_to_string(file_path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+            eprintln!("Error parsing speaker catalogue {}: {}. Using no speakers.", file_path, err);
+            Speakers::new()
+        }),
+        Err(_) => Speakers::new(),
+    }
+}
+
+pub fn save_speakers(speakers: &Speakers, file_path: &str) -> Result<(), io::Error> {
+    let mut json = serde_json::to_string_pretty(speakers)?;
+    json.push('\n');
+    fs::write(file_path, json)
+}
+
+pub fn find_speaker<'a>(speakers: &'a Speakers, id: &str) -> Option<&'a Speaker> {
+    if id.is_empty() {
+        return speakers.values().find(|speaker| speaker.kind == SpeakerKind::Narrator);
+    }
+    speakers.get(id)
+}
+
+// What the player sees for a speaker ID; unknown IDs are shown as written
+pub fn display_name<'a>(speakers: &'a Speakers, id: &'a str) -> &'a str {
+    find_speaker(speakers, id).map_or(id, |speaker| speaker.name.as_str())
+}
+
+// The speaker's name in their color, ready for a label; narration has no name to show
+pub fn speaker_label(speakers: &Speakers, id: &str) -> Option<egui::RichText> {
+    let speaker = find_speaker(speakers, id);
+    if speaker.is_some_and(|speaker| speaker.kind == SpeakerKind::Narrator) || (id.is_empty() && speaker.is_none()) {
+        return None;
+    }
+    let mut text = egui::RichText::new(display_name(speakers, id)).strong();
+    if let Some(color) = speaker.and_then(Speaker::text_color) {
+        text = text.color(color);
+    }
+    Some(text)
+}
+
+// Speaker IDs used by the dialogue, or its passive checks, that the catalogue doesn't have
+pub fn unknown_speakers<'a>(dialogue: &'a Dialogue, speakers: &Speakers) -> Vec<&'a str> {
+    std::iter::once(dialogue.speaker.as_str())
+        .chain(dialogue.passive_check.iter().filter_map(|check| check.speaker.as_deref()))
+        .filter(|id| !id.is_empty() && !speakers.contains_key(*id))
+        .collect()
+}
@@ -0,0 +1,286 @@
+// Numbers for planning the writing: how much text there is, how much it branches and how much is left to write
+// Used by the `stats` command, which prints them as text or JSON
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::analysis::entry_points;
+use crate::markup::dialogue_texts;
+use crate::project::Project;
+use crate::{Dialogue, Dialogues};
+
+// Roughly how fast a player reads, for the reading time estimate
+pub const WORDS_PER_MINUTE: f64 = 200.0;
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct ConversationStats {
+    pub conversation: String,
+    pub dialogues: usize,
+    pub words: usize,                    // Intro, option and passive check text, markup included
+    pub reading_minutes: f64,
+    pub options: usize,
+    pub average_options: f64,            // Options per dialogue
+    pub branching_factor: f64,           // Distinct dialogues linked per dialogue that links anywhere
+    pub checks: BTreeMap<String, usize>, // Challenges and passive checks by skill
+    pub longest_path: usize,             // Dialogues on the longest way through from the entry points, see longest_path
+    pub placeholders: usize,             // Dialogues still saying "New Speaker" or "New Intro Text"
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+pub struct ProjectStats {
+    pub conversations: Vec<ConversationStats>,
+    pub total: ConversationStats, // Sums, except the averages and the longest path, which is the longest of any conversation
+}
+
+fn words(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+fn ratio(count: usize, of: usize) -> f64 {
+    if of == 0 {
+        0.0
+    } else {
+        count as f64 / of as f64
+    }
+}
+
+// Groups of dialogues that can all reach each other (Tarjan's algorithm): each dialogue's group, and each group's size
+// A dialogue on no loop is a group of its own
+fn loops(dialogues: &Dialogues) -> (HashMap<&str, usize>, Vec<usize>) {
+    #[derive(Default)]
+    struct Search<'a> {
+        order: HashMap<&'a str, usize>, // When each dialogue was first visited
+        low: HashMap<&'a str, usize>,   // The earliest visited dialogue it can get back to
+        stack: Vec<&'a str>,
+        group: HashMap<&'a str, usize>,
+        sizes: Vec<usize>,
+    }
+
+    fn visit<'a>(dialogues: &'a Dialogues, id: &'a str, search: &mut Search<'a>) {
+        let order = search.order.len();
+        search.order.insert(id, order);
+        search.low.insert(id, order);
+        search.stack.push(id);
+
+        for target in dialogues[id].links().filter(|target| dialogues.contains_key(*target)) {
+            if !search.order.contains_key(target) {
+                visit(dialogues, target, search);
+                let low = search.low[target].min(search.low[id]);
+                search.low.insert(id, low);
+            } else if !search.group.contains_key(target) {
+                // Still on the stack, so part of the loop being built
+                let low = search.order[target].min(search.low[id]);
+                search.low.insert(id, low);
+            }
+        }
+
+        if search.low[id] == order {
+            let group = search.sizes.len();
+            let mut size = 0;
+            while let Some(member) = search.stack.pop() {
+                search.group.insert(member, group);
+                size += 1;
+                if member == id {
+                    break;
+                }
+            }
+            search.sizes.push(size);
+        }
+    }
+
+    let mut search = Search::default();
+    for id in dialogues.keys() {
+        if !search.order.contains_key(id.as_str()) {
+            visit(dialogues, id, &mut search);
+        }
+    }
+    (search.group, search.sizes)
+}
+
+// How many dialogues a player can see on the way through from an entry point
+// The longest path that never repeats a dialogue is too costly to find once there are loops, so a loop counts
+// as every dialogue in it and the longest route between loops is taken; links to other conversations end the path
+pub fn longest_path(dialogues: &Dialogues) -> usize {
+    fn deepest(group: usize, next: &[HashSet<usize>], sizes: &[usize], known: &mut HashMap<usize, usize>) -> usize {
+        if let Some(&depth) = known.get(&group) {
+            return depth;
+        }
+        let depth = sizes[group] + next[group].iter().map(|&to| deepest(to, next, sizes, known)).max().unwrap_or(0);
+        known.insert(group, depth);
+        depth
+    }
+
+    let (group, sizes) = loops(dialogues);
+    // Links between groups never loop, so each group's depth can be remembered once it's known
+    let mut next = vec![HashSet::new(); sizes.len()];
+    for (id, dialogue) in dialogues {
+        let from = group[id.as_str()];
+        for target in dialogue.links() {
+            if let Some(&to) = group.get(target).filter(|&&to| to != from) {
+                next[from].insert(to);
+            }
+        }
+    }
+
+    let mut known = HashMap::new();
+    entry_points(dialogues)
+        .iter()
+        .filter_map(|entry| group.get(entry.as_str()))
+        .map(|&entry| deepest(entry, &next, &sizes, &mut known))
+        .max()
+        .unwrap_or(0)
+}
+
+// Distinct dialogues linked per dialogue, counting only dialogues that link somewhere
+fn branching_factor<'a>(dialogues: impl Iterator<Item = &'a Dialogue>) -> f64 {
+    let (mut linking, mut links) = (0, 0);
+    for dialogue in dialogues {
+        let targets: HashSet<&str> = dialogue.links().collect();
+        if !targets.is_empty() {
+            linking += 1;
+            links += targets.len();
+        }
+    }
+    ratio(links, linking)
+}
+
+fn skills_checked(dialogue: &Dialogue) -> impl Iterator<Item = String> + '_ {
+    let challenges = dialogue.options.iter().filter_map(|option| option.challenge_attribute.as_deref());
+    let passive = dialogue.passive_check.iter().map(|check| check.skill.as_str());
+    challenges.chain(passive).map(str::to_lowercase)
+}
+
+pub fn conversation_stats(conversation: &str, dialogues: &Dialogues) -> ConversationStats {
+    let mut stats = ConversationStats {
+        conversation: conversation.to_string(),
+        dialogues: dialogues.len(),
+        longest_path: longest_path(dialogues),
+        ..Default::default()
+    };
+
+    for dialogue in dialogues.values() {
+        stats.words += dialogue_texts(dialogue).map(words).sum::<usize>();
+        stats.options += dialogue.options.len();
+        for skill in skills_checked(dialogue) {
+            *stats.checks.entry(skill).or_default() += 1;
+        }
+        if dialogue.is_placeholder() {
+            stats.placeholders += 1;
+        }
+    }
+    stats.reading_minutes = stats.words as f64 / WORDS_PER_MINUTE;
+    stats.average_options = ratio(stats.options, stats.dialogues);
+    stats.branching_factor = branching_factor(dialogues.values());
+    stats
+}
+
+// Every conversation of the project in project order, and the totals
+pub fn project_stats(project: &Project, project_path: &str, conversations: &HashMap<String, Dialogues>) -> ProjectStats {
+    let per_conversation: Vec<ConversationStats> = project
+        .conversation_files(project_path)
+        .iter()
+        .filter_map(|file| Some(conversation_stats(&file.conversation, conversations.get(&file.conversation)?)))
+        .collect();
+
+    let mut total = ConversationStats {
+        conversation: "Total".to_string(),
+        ..Default::default()
+    };
+    for stats in &per_conversation {
+        total.dialogues += stats.dialogues;
+        total.words += stats.words;
+        total.options += stats.options;
+        total.placeholders += stats.placeholders;
+        total.longest_path = total.longest_path.max(stats.longest_path);
+        for (skill, count) in &stats.checks {
+            *total.checks.entry(skill.clone()).or_default() += count;
+        }
+    }
+    total.reading_minutes = total.words as f64 / WORDS_PER_MINUTE;
+    // Averages over every dialogue of the project, not over the conversations' averages
+    total.average_options = ratio(total.options, total.dialogues);
+    total.branching_factor = branching_factor(conversations.values().flat_map(|dialogues| dialogues.values()));
+
+    ProjectStats {
+        conversations: per_conversation,
+        total,
+    }
+}
+
+impl fmt::Display for ConversationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.conversation)?;
+        writeln!(f, "  Dialogues: {} ({} still placeholders)", self.dialogues, self.placeholders)?;
+        writeln!(f, "  Words: {} (about {:.1} min to read)", self.words, self.reading_minutes)?;
+        writeln!(f, "  Options: {} ({:.2} per dialogue)", self.options, self.average_options)?;
+        writeln!(f, "  Branching factor: {:.2}", self.branching_factor)?;
+        writeln!(f, "  Longest path: {} dialogue(s)", self.longest_path)?;
+        if self.checks.is_empty() {
+            writeln!(f, "  Checks: none")?;
+        } else {
+            let checks: Vec<String> = self.checks.iter().map(|(skill, count)| format!("{} {}", skill, count)).collect();
+            writeln!(f, "  Checks: {}", checks.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ProjectStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stats in &self.conversations {
+            writeln!(f, "{}", stats)?;
+        }
+        write!(f, "{}", self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DialogueOption;
+
+    fn file(links: &[(&str, &[&str])]) -> Dialogues {
+        links
+            .iter()
+            .map(|(id, targets)| {
+                let options = targets
+                    .iter()
+                    .map(|target| DialogueOption {
+                        success_dialogue: Some(target.to_string()),
+                        ..Default::default()
+                    })
+                    .collect();
+                (id.to_string(), Dialogue { options, ..Default::default() })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loops_count_as_all_their_dialogues() {
+        // Start leads into the loop A -> B -> C -> A, which has a way out to End
+        let dialogues = file(&[
+            ("Start", &["B", "A"]),
+            ("A", &["B"]),
+            ("B", &["C"]),
+            ("C", &["A", "End"]),
+            ("End", &["Intro/Start"]),
+        ]);
+        assert_eq!(longest_path(&dialogues), 5);
+
+        // The same file in another order gives the same answer
+        let mut reversed = dialogues.clone();
+        reversed.reverse();
+        reversed.move_index(reversed.get_index_of("Start").unwrap(), 0);
+        assert_eq!(longest_path(&reversed), 5);
+    }
+
+    #[test]
+    fn branches_take_the_longer_side() {
+        let dialogues = file(&[("Start", &["Short", "Long"]), ("Short", &[]), ("Long", &["Longer"]), ("Longer", &["Long"])]);
+        assert_eq!(longest_path(&dialogues), 3);
+        assert_eq!(longest_path(&file(&[("Start", &["Start"])])), 1);
+        assert_eq!(longest_path(&Dialogues::new()), 0);
+    }
+}
@@ -0,0 +1,115 @@
+// Plays the game in a terminal: dialogue text is styled with ANSI escape codes and choices are typed as numbers
+
+use std::io::{self, BufRead, Write};
+
+use shadow_soldiers_dialog::markup::{speaker_to_ansi, to_ansi, MarkupContext};
+use shadow_soldiers_dialog::DialogueOption;
+
+use super::DialogueApp;
+
+enum Choice {
+    Option(DialogueOption),
+    Talk(String),
+    Travel(String),
+    Language(Option<String>), // Typed as "lang de", or just "lang" for the source text
+}
+
+pub fn run(app: &mut DialogueApp) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut out = io::stdout();
+
+    loop {
+        let mut choices = vec![];
+        {
+            let game = &app.game;
+            let variables = |name: &str| game.variable(name);
+            let has = |condition: &str| game.player.has(condition);
+            let context = MarkupContext {
+                speakers: &app.speakers,
+                variables: &variables,
+                has: &has,
+            };
+
+            writeln!(out, "\n[{} - {}]", game.time, game.position.location)?;
+            if let Some(dialogue) = game.dialogue() {
+                let intro = to_ansi(app.markup.get(&dialogue.intro), &context);
+                match speaker_to_ansi(&dialogue.speaker, &context) {
+                    Some(name) => writeln!(out, "{}: {}", name, intro)?,
+                    None => writeln!(out, "{}", intro)?,
+                }
+                for option in &dialogue.options {
+                    let description = to_ansi(app.markup.get(&option.description), &context);
+                    let label = match &option.challenge_attribute {
+                        Some(skill) => format!("[{} {}] {}", skill, option.challenge_number.unwrap_or(0), description),
+                        None => description,
+                    };
+                    choices.push((label, Choice::Option(option.clone())));
+                }
+            } else {
+                for conversation in game.conversations() {
+                    choices.push((format!("Talk: {}", conversation), Choice::Talk(conversation.clone())));
+                }
+                for exit in game.exits() {
+                    let label = format!("Go to {} ({} min)", exit.to, exit.time_cost.unwrap_or(0));
+                    choices.push((label, Choice::Travel(exit.to.clone())));
+                }
+            }
+        }
+
+        if choices.is_empty() {
+            writeln!(out, "Nowhere to go from here.")?;
+            return Ok(());
+        }
+        for (i, (label, _)) in choices.iter().enumerate() {
+            writeln!(out, "{}. {}", i + 1, label)?;
+        }
+
+        // Ask until we get a number in range or a language; q or end of input quits
+        let choice = loop {
+            write!(out, "> ")?;
+            out.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                return Ok(());
+            };
+            let line = line.trim();
+            if line == "q" {
+                return Ok(());
+            }
+            if let Some(name) = line.strip_prefix("lang") {
+                let name = name.trim();
+                break Choice::Language((!name.is_empty()).then(|| name.to_string()));
+            }
+            match line.parse::<usize>() {
+                Ok(n) if (1..=choices.len()).contains(&n) => break choices.swap_remove(n - 1).1,
+                _ => writeln!(out, "Pick a number from 1 to {}, lang to switch language, or q to quit.", choices.len())?,
+            }
+        };
+
+        let result = match choice {
+            Choice::Option(option) => app.choose_option(&option),
+            Choice::Talk(conversation) => app.talk(&conversation),
+            Choice::Travel(to) => app.travel(&to),
+            Choice::Language(name) => {
+                if let Err(err) = app.set_language(name.as_deref()) {
+                    writeln!(out, "Couldn't load the translation: {}", err)?;
+                }
+                continue;
+            }
+        };
+        match result {
+            Ok(turn) => {
+                if let Some(roll) = &turn.roll {
+                    writeln!(out, "{}", roll)?;
+                }
+                if turn.xp > 0 {
+                    writeln!(out, "You gained {} XP.", turn.xp)?;
+                }
+                if turn.skill_points > 0 {
+                    writeln!(out, "You gained a skill point! You now have {} skill points.", app.game.player.skill_points)?;
+                }
+            }
+            Err(err) => writeln!(out, "{}", err)?,
+        }
+    }
+}
@@ -0,0 +1,367 @@
+// Golden transcripts: a scripted playthrough of a conversation, and everything the game showed while playing it
+// A transcript file starts with a header and holds the player's input on "> " lines:
+//
+//   # Failing to see through the clock
+//   conversation: Clock
+//   build: 3,3,3,3
+//   start: ClockInspect
+//
+//   == ClockInspect (Day 1, 03:30)
+//   Grandfather Clock: ...
+//     1. [delusion 10] ...
+//   > 1 roll 2 3
+//     delusion 10: 2 + 3 + 3 = 8, failed
+//   ...
+//
+// Everything else is output. Replaying reads the header and inputs, plays them against the current content
+// with the dice the script gives, and compares the result with the file, so any change to text, options or
+// outcomes shows up as a diff. `start` is optional and defaults to the conversation's first entry point,
+// `project` names a project file other than the default one, and `language` plays it translated
+
+use std::fmt;
+use std::fs;
+
+use std::collections::HashMap;
+
+use crate::exploration::EndKind;
+use crate::localization::Language;
+use crate::markup::{to_plain, MarkupCache, MarkupContext};
+use crate::navigation::{NavigationError, Outcome, Position};
+use crate::player::parse_build;
+use crate::project::{Project, DEFAULT_PROJECT_FILE};
+use crate::runtime::Game;
+use crate::speakers::{load_speakers, speaker_label, speaker_path, Speakers};
+use crate::Location;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Input {
+    pub choice: usize, // 1-based, among the options the player can see
+    pub dice: Option<(i32, i32)>,
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dice {
+            Some((die1, die2)) => write!(f, "> {} roll {} {}", self.choice, die1, die2),
+            None => write!(f, "> {}", self.choice),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub comments: Vec<String>, // "#" lines at the top, kept as written
+    pub project: Option<String>,
+    pub conversation: String,
+    pub build: String, // As written, e.g. "3,3,3,3"
+    pub start: Option<String>,
+    pub language: Option<String>, // Plays with that language's string table
+    pub inputs: Vec<Input>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize, // 1-based
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+fn parse_input(text: &str) -> Result<Input, String> {
+    let mut words = text.split_whitespace();
+    let choice = words
+        .next()
+        .and_then(|word| word.parse().ok())
+        .ok_or("an input starts with the number of the option to pick")?;
+    let dice = match words.next() {
+        None => None,
+        Some("roll") => {
+            let mut die = || words.next().and_then(|word| word.parse::<i32>().ok()).filter(|die| (1..=6).contains(die));
+            match (die(), die()) {
+                (Some(die1), Some(die2)) => Some((die1, die2)),
+                _ => return Err("'roll' is followed by two dice from 1 to 6".to_string()),
+            }
+        }
+        Some(word) => return Err(format!("expected 'roll', found '{}'", word)),
+    };
+    if words.next().is_some() {
+        return Err("unexpected text after the input".to_string());
+    }
+    Ok(Input { choice, dice })
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Self, ScriptError> {
+        let mut script = Script {
+            comments: vec![],
+            project: None,
+            conversation: String::new(),
+            build: String::new(),
+            start: None,
+            language: None,
+            inputs: vec![],
+        };
+        let mut in_header = true;
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| ScriptError { line: i + 1, message };
+            let line = line.trim_end();
+
+            if let Some(input) = line.strip_prefix("> ") {
+                script.inputs.push(parse_input(input).map_err(error)?);
+                in_header = false;
+                continue;
+            }
+            if !in_header || line.is_empty() {
+                in_header = in_header && script.conversation.is_empty();
+                continue;
+            }
+            if line.starts_with('#') {
+                script.comments.push(line.to_string());
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                in_header = false;
+                continue;
+            };
+            let value = value.trim().to_string();
+            match key.trim() {
+                "project" => script.project = Some(value),
+                "conversation" => script.conversation = value,
+                "build" => script.build = value,
+                "start" => script.start = Some(value),
+                "language" => script.language = Some(value),
+                _ => in_header = false,
+            }
+        }
+
+        if script.conversation.is_empty() {
+            return Err(ScriptError {
+                line: 1,
+                message: "the header needs a 'conversation:' line".to_string(),
+            });
+        }
+        if script.build.is_empty() {
+            script.build = "3,3,3,3".to_string();
+        }
+        Ok(script)
+    }
+
+    pub fn project_path(&self) -> &str {
+        self.project.as_deref().unwrap_or(DEFAULT_PROJECT_FILE)
+    }
+
+    fn header(&self) -> String {
+        let mut lines = self.comments.clone();
+        if let Some(project) = &self.project {
+            lines.push(format!("project: {}", project));
+        }
+        lines.push(format!("conversation: {}", self.conversation));
+        lines.push(format!("build: {}", self.build));
+        if let Some(start) = &self.start {
+            lines.push(format!("start: {}", start));
+        }
+        if let Some(language) = &self.language {
+            lines.push(format!("language: {}", language));
+        }
+        lines.join("\n")
+    }
+}
+
+// Plays the script and writes out the whole transcript, header included
+// It plays through runtime::Game like the game itself, so text is localized and rendered the way the player reads it.
+// Problems with the script itself, like picking an option that isn't there, are written into the
+// transcript as "!!" lines, so they show up in the diff like any other change
+pub fn play(script: &Script, project: &Project, locations: HashMap<String, Location>, speakers: &Speakers) -> String {
+    let mut out = vec![script.header(), String::new()];
+    let finish = |mut out: Vec<String>, line: String| {
+        out.push(line);
+        out.join("\n") + "\n"
+    };
+    let player = match parse_build(&script.build) {
+        Ok(player) => player,
+        Err(err) => return finish(out, format!("!! bad build: {}", err)),
+    };
+    let Some(location) = project.location_of(&script.conversation) else {
+        return finish(out, format!("!! no conversation '{}' in the project", script.conversation));
+    };
+    let mut game = Game::new(locations, Position::at(location), player);
+    if let Some(language) = &script.language {
+        match Language::load(script.project_path(), language) {
+            Ok(language) => game.language = Some(language),
+            Err(err) => return finish(out, format!("!! can't load language {}: {}", language, err)),
+        }
+    }
+    game.start();
+    let started = match &script.start {
+        Some(start) => game.go_to(&format!("{}/{}", script.conversation, start)),
+        None => game.talk(&script.conversation),
+    };
+    let mut turn = match started {
+        Ok(turn) => turn,
+        Err(err) => return finish(out, format!("!! {}", err)),
+    };
+
+    let mut markup = MarkupCache::new();
+    let mut inputs = script.inputs.iter();
+    let ending = loop {
+        let (Some(id), Some(dialogue)) = (game.position.dialogue.clone(), game.dialogue()) else {
+            break "!! not in a conversation".to_string();
+        };
+        let dialogue = dialogue.into_owned();
+        out.push(format!("== {} ({})", id, game.time));
+        if turn.xp > 0 {
+            out.push(format!("  + {} XP", turn.xp));
+        }
+        {
+            let variables = |name: &str| game.variable(name);
+            let has = |condition: &str| game.player.has(condition);
+            let context = MarkupContext {
+                speakers,
+                variables: &variables,
+                has: &has,
+            };
+            let intro = to_plain(markup.get(&dialogue.intro), &context);
+            match speaker_label(speakers, &dialogue.speaker) {
+                Some(name) => out.push(format!("{}: {}", name.text(), intro)),
+                None => out.push(intro),
+            }
+            for (i, option) in dialogue.options.iter().enumerate() {
+                let description = to_plain(markup.get(&option.description), &context);
+                match (&option.challenge_attribute, option.challenge_number) {
+                    (Some(skill), Some(target)) => out.push(format!("  {}. [{} {}] {}", i + 1, skill, target, description)),
+                    _ => out.push(format!("  {}. {}", i + 1, description)),
+                }
+            }
+        }
+        if dialogue.options.is_empty() {
+            break "-- stuck: no options to pick".to_string();
+        }
+
+        let Some(input) = inputs.next() else {
+            break "-- end of script".to_string();
+        };
+        out.push(input.to_string());
+        let Some(option) = input.choice.checked_sub(1).and_then(|i| dialogue.options.get(i)) else {
+            out.push(format!("!! there's no option {}", input.choice));
+            break "-- end of script".to_string();
+        };
+        if game.player.challenge(option).is_some() && input.dice.is_none() {
+            out.push(format!("!! option {} is a challenge, so the input needs 'roll' and two dice", input.choice));
+            break "-- end of script".to_string();
+        }
+
+        let conversation = game.position.conversation.clone().unwrap_or_default();
+        turn = match game.choose(option, || input.dice.unwrap_or_default()) {
+            Ok(turn) => turn,
+            Err(NavigationError::UnknownDialogue(link)) => break format!("-- {}", EndKind::Missing(link)),
+            Err(err) => break format!("!! {}", err),
+        };
+        if let Some(roll) = &turn.roll {
+            out.push(format!("  {}", roll));
+        }
+        for flag in &turn.flags {
+            out.push(format!("  + flag {}", flag));
+        }
+        for item in &turn.items {
+            out.push(format!("  + item {}", item));
+        }
+
+        let success = turn.roll.as_ref().is_none_or(|roll| roll.success);
+        match option.outcome(success, &conversation) {
+            Outcome::Dialogue(_) => {}
+            Outcome::Link(link) => break format!("-- {}", EndKind::Link(link.to_string())),
+            Outcome::Leave => break format!("-- {}", EndKind::Leave),
+            Outcome::MoveTo(location) => break format!("-- {}", EndKind::MoveTo(location.to_string())),
+        }
+    };
+    out.push(ending);
+
+    let unused = inputs.count();
+    if unused > 0 {
+        out.push(format!("!! {} input(s) left over", unused));
+    }
+    out.join("\n") + "\n"
+}
+
+// A line diff of the expected transcript against the actual one: "-" lines were expected, "+" lines came out instead
+pub fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence, filled from the end so the walk below goes forwards
+    let mut common = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            out.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || common[i + 1][j] >= common[i][j + 1]) {
+            out.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    out.join("\n")
+}
+
+// Replays a transcript file against the project's current content
+// Returns the transcript as it plays now, and the diff if it doesn't match the file
+pub fn replay(path: &str) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let expected = fs::read_to_string(path)?;
+    let script = Script::parse(&expected)?;
+    let project = Project::load(script.project_path())?;
+    let locations = project.load_locations(script.project_path());
+    let actual = play(&script, &project, locations, &load_speakers(&speaker_path(script.project_path())));
+    let difference = (actual != expected).then(|| diff(&expected, &actual));
+    Ok((actual, difference))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let expected = "== Start\nHello.\n  1. Bye.\n";
+        let actual = "== Start\nHello there.\n  1. Bye.\n";
+        assert_eq!(diff(expected, actual), "  == Start\n- Hello.\n+ Hello there.\n    1. Bye.");
+    }
+
+    #[test]
+    fn inputs_are_parsed_from_the_body() {
+        let script = Script::parse("# A comment\nconversation: Clock\nbuild: 6,2,2,2\n\n== ClockInspect\n> 2 roll 6 6\n> 1\n").unwrap();
+        assert_eq!(script.comments, vec!["# A comment"]);
+        assert_eq!(script.build, "6,2,2,2");
+        assert_eq!(
+            script.inputs,
+            vec![
+                Input {
+                    choice: 2,
+                    dice: Some((6, 6))
+                },
+                Input { choice: 1, dice: None }
+            ]
+        );
+        assert!(Script::parse("> 1 roll 7 1\n").is_err());
+    }
+}
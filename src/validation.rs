@@ -0,0 +1,99 @@
+// Content checks over a whole project: links, speakers, markup and the variables it uses, and stubs players can reach
+// Used by the `check` command and by the editor to list what's wrong with the selected dialogue
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::analysis::{reachable, Completeness};
+use crate::markup::{labelled_texts, Markup};
+use crate::project::{broken_links, Project};
+use crate::speakers::{unknown_speakers, Speakers};
+use crate::{Dialogue, Dialogues};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub conversation: String,
+    pub dialogue: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}: {}", self.conversation, self.dialogue, self.message)
+    }
+}
+
+// Everything wrong with one dialogue, as messages for the editor
+pub fn dialogue_issues(
+    dialogue: &Dialogue,
+    local: &Dialogues,
+    conversations: &HashMap<String, &Dialogues>,
+    speakers: &Speakers,
+) -> Vec<String> {
+    let mut messages = vec![];
+    for link in broken_links(dialogue, local, conversations) {
+        messages.push(format!("links to missing dialogue '{}'", link));
+    }
+    for speaker in unknown_speakers(dialogue, speakers) {
+        messages.push(format!("unknown speaker '{}'", speaker));
+    }
+    for (field, text) in labelled_texts(dialogue) {
+        match Markup::parse(text) {
+            Ok(markup) => {
+                for speaker in markup.speakers().into_iter().filter(|id| !speakers.contains_key(*id)) {
+                    messages.push(format!("{}: refers to unknown speaker '{}'", field, speaker));
+                }
+                for variable in markup.unknown_variables() {
+                    messages.push(format!("{}: unknown variable '{}'", field, variable));
+                }
+            }
+            Err(err) => messages.push(format!("{}: {}", field, err)),
+        }
+    }
+    messages
+}
+
+// How many dialogues of the project are stubs, incomplete and complete
+pub fn completeness_counts(conversations: &HashMap<String, Dialogues>) -> (usize, usize, usize) {
+    let mut counts = (0, 0, 0);
+    for dialogue in conversations.values().flat_map(|dialogues| dialogues.values()) {
+        match dialogue.completeness() {
+            Completeness::Stub => counts.0 += 1,
+            Completeness::Incomplete(_) => counts.1 += 1,
+            Completeness::Complete => counts.2 += 1,
+        }
+    }
+    counts
+}
+
+// Every issue in every conversation of the project, in project order
+pub fn validate_conversations(
+    project: &Project,
+    project_path: &str,
+    conversations: &HashMap<String, Dialogues>,
+    speakers: &Speakers,
+) -> Vec<Issue> {
+    let lookup: HashMap<String, &Dialogues> = conversations.iter().map(|(id, dialogues)| (id.clone(), dialogues)).collect();
+    let mut issues = vec![];
+    for file in project.conversation_files(project_path) {
+        let Some(dialogues) = conversations.get(&file.conversation) else {
+            continue;
+        };
+        // Unwritten dialogues are fine while nothing leads to them; a player landing on one gets an empty screen
+        let reached = reachable(dialogues);
+        for (id, dialogue) in dialogues {
+            let mut messages = dialogue_issues(dialogue, dialogues, &lookup, speakers);
+            if reached.contains(id) && dialogue.completeness() == Completeness::Stub {
+                messages.push("is a stub but can be reached from the entry points".to_string());
+            }
+            for message in messages {
+                issues.push(Issue {
+                    conversation: file.conversation.clone(),
+                    dialogue: id.clone(),
+                    message,
+                });
+            }
+        }
+    }
+    issues
+}
@@ -0,0 +1,24 @@
+// Replays every golden transcript in tests/transcripts against the current content
+// After an intended content change, rewrite them with `cargo run -- transcript tests/transcripts/*.txt --update`
+
+use std::fs;
+
+use shadow_soldiers_dialog::transcript::replay;
+
+#[test]
+fn transcripts_match_current_content() {
+    let mut failures = vec![];
+    for entry in fs::read_dir("tests/transcripts").expect("tests/transcripts should exist") {
+        let path = entry.expect("readable directory entry").path();
+        if path.extension().is_none_or(|extension| extension != "txt") {
+            continue;
+        }
+        let path = path.to_string_lossy().into_owned();
+        match replay(&path) {
+            Ok((_, None)) => {}
+            Ok((_, Some(difference))) => failures.push(format!("{} changed:\n{}", path, difference)),
+            Err(err) => failures.push(format!("{}: {}", path, err)),
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}